@@ -0,0 +1,269 @@
+use crate::bitset::{BitSet, HeapBitSet};
+use rustc_hash::FxHashMap;
+
+/// Which answers to pick in a group, based on how many people in it gave them.
+#[derive(Clone, Debug)]
+pub enum Query {
+    Anyone,
+    Everyone,
+    AtLeast(usize),
+    Exactly(usize),
+    /// Answers given by an odd number of people, like chaining symmetric differences.
+    Odd,
+    Union(Box<Query>, Box<Query>),
+    Intersection(Box<Query>, Box<Query>),
+    Difference(Box<Query>, Box<Query>),
+    SymmetricDifference(Box<Query>, Box<Query>),
+}
+
+impl Query {
+    /// Reads an expression like `(anyone - at-least 2) | everyone`, with the binary
+    /// operators `|`, `&`, `-` and `^` applied from left to right.
+    pub fn parse(expr: &str) -> Result<Query, String> {
+        let spaced = expr.replace('(', " ( ").replace(')', " ) ");
+        let tokens: Vec<&str> = spaced.split_whitespace().collect();
+
+        let mut pos = 0;
+        let query = Query::parse_expr(&tokens, &mut pos)?;
+        match tokens.get(pos) {
+            None => Ok(query),
+            Some(token) => Err(format!("unexpected {}", token)),
+        }
+    }
+
+    fn parse_expr(tokens: &[&str], pos: &mut usize) -> Result<Query, String> {
+        let mut query = Query::parse_term(tokens, pos)?;
+
+        while let Some(op) = tokens.get(*pos) {
+            let combine = match *op {
+                "|" => Query::Union,
+                "&" => Query::Intersection,
+                "-" => Query::Difference,
+                "^" => Query::SymmetricDifference,
+                _ => break,
+            };
+            *pos += 1;
+            query = combine(Box::new(query), Box::new(Query::parse_term(tokens, pos)?));
+        }
+
+        Ok(query)
+    }
+
+    fn parse_term(tokens: &[&str], pos: &mut usize) -> Result<Query, String> {
+        let token = *tokens.get(*pos).ok_or("missing query")?;
+        *pos += 1;
+
+        let mut number = || -> Result<usize, String> {
+            let n = tokens
+                .get(*pos)
+                .ok_or(format!("{} needs a number", token))?;
+            *pos += 1;
+            n.parse().map_err(|_| format!("not a number: {}", n))
+        };
+
+        match token {
+            "anyone" => Ok(Query::Anyone),
+            "everyone" => Ok(Query::Everyone),
+            "odd" => Ok(Query::Odd),
+            "at-least" => Ok(Query::AtLeast(number()?)),
+            "exactly" => Ok(Query::Exactly(number()?)),
+            "(" => {
+                let query = Query::parse_expr(tokens, pos)?;
+                if tokens.get(*pos) != Some(&")") {
+                    return Err("missing )".to_owned());
+                }
+                *pos += 1;
+                Ok(query)
+            }
+            _ => Err(format!("unknown query: {}", token)),
+        }
+    }
+
+    fn eval(&self, counts: &[usize], size: usize) -> HeapBitSet {
+        let pick = |f: &dyn Fn(usize) -> bool| {
+            counts
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| f(**c))
+                .map(|(i, _)| i)
+                .collect()
+        };
+
+        match self {
+            Query::Anyone => pick(&|c| c > 0),
+            Query::Everyone => pick(&|c| c == size),
+            Query::AtLeast(k) => pick(&|c| c >= *k),
+            Query::Exactly(k) => pick(&|c| c == *k),
+            Query::Odd => pick(&|c| c % 2 == 1),
+            Query::Union(a, b) => &a.eval(counts, size) | &b.eval(counts, size),
+            Query::Intersection(a, b) => &a.eval(counts, size) & &b.eval(counts, size),
+            Query::Difference(a, b) => &a.eval(counts, size) - &b.eval(counts, size),
+            Query::SymmetricDifference(a, b) => &a.eval(counts, size) ^ &b.eval(counts, size),
+        }
+    }
+}
+
+/// Groups of people separated by blank lines, with the answers of one person per line.
+pub struct GroupSet {
+    groups: Vec<Group>,
+    /// Indexes into the alphabet.
+    answers: Vec<usize>,
+    alphabet: Vec<char>,
+}
+
+#[derive(Debug)]
+pub struct Group {
+    pos: usize,
+    len: usize,
+    size: usize,
+}
+
+impl GroupSet {
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// The answers the query picks for the group, as indexes into the alphabet.
+    pub fn eval(&self, group: &Group, query: &Query) -> HeapBitSet {
+        let mut counts = vec![0; self.alphabet.len()];
+        for a in self.answers[group.pos..group.pos + group.len].iter() {
+            counts[*a] += 1;
+        }
+
+        query.eval(&counts, group.size)
+    }
+
+    /// The number of answers the query picks, summed over all groups.
+    pub fn count(&self, query: &Query) -> usize {
+        self.groups.iter().map(|g| self.eval(g, query).len()).sum()
+    }
+
+    /// The answers the query picks for the group, in alphabet order.
+    pub fn answers(&self, group: &Group, query: &Query) -> Vec<char> {
+        self.eval(group, query)
+            .iter()
+            .map(|i| self.alphabet[i])
+            .collect()
+    }
+
+    pub fn parse(s: &str) -> GroupSet {
+        let mut groups = Vec::with_capacity(s.len() / 4);
+        let mut group_idx = 0usize;
+        let mut answers = Vec::with_capacity(s.len());
+        let mut alphabet = Vec::with_capacity(26);
+        let mut indexes: FxHashMap<char, usize> = FxHashMap::default();
+        let mut person_pos = 0;
+
+        groups.push(Group {
+            len: 0,
+            pos: 0,
+            size: 0,
+        });
+
+        let mut p = ' ';
+        for c in s.chars() {
+            if c == '\n' {
+                if p == '\n' {
+                    groups[group_idx].len = answers.len() - groups[group_idx].pos;
+                    groups.push(Group {
+                        pos: answers.len(),
+                        size: 0,
+                        len: 0,
+                    });
+                    group_idx += 1;
+                } else {
+                    groups[group_idx].size += 1;
+                }
+                person_pos = answers.len();
+            } else if !c.is_whitespace() {
+                let index = *indexes.entry(c).or_insert_with(|| {
+                    alphabet.push(c);
+                    alphabet.len() - 1
+                });
+
+                // Someone giving the same answer twice still counts once.
+                if !answers[person_pos..].contains(&index) {
+                    answers.push(index);
+                }
+            }
+
+            p = c;
+        }
+
+        groups[group_idx].len = answers.len() - groups[group_idx].pos;
+
+        // A trailing newline doesn't end a person's answers like a blank line does.
+        if !s.ends_with('\n') {
+            groups[group_idx].size += 1;
+        }
+
+        // Trailing blank lines, or several in a row, leave groups with nobody in them.
+        groups.retain(|g| g.size > 0);
+
+        // Sort the alphabet, so that answer indexes follow it.
+        let mut order: Vec<usize> = (0..alphabet.len()).collect();
+        order.sort_by_key(|i| alphabet[*i]);
+        let mut remap = vec![0; order.len()];
+        for (new, old) in order.iter().enumerate() {
+            remap[*old] = new;
+        }
+        for a in answers.iter_mut() {
+            *a = remap[*a];
+        }
+        alphabet.sort_unstable();
+
+        GroupSet {
+            groups,
+            answers,
+            alphabet,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queries() {
+        let gs = GroupSet::parse("xy+Z\nx+\nxZ\n\n\u{e9}\u{e9}\n");
+        assert_eq!(gs.alphabet, vec!['+', 'Z', 'x', 'y', '\u{e9}']);
+
+        let group = &gs.groups[0];
+        let answers = |q: Query| gs.answers(group, &q).into_iter().collect::<String>();
+        assert_eq!(answers(Query::Anyone), "+Zxy");
+        assert_eq!(answers(Query::Everyone), "x");
+        assert_eq!(answers(Query::AtLeast(2)), "+Zx");
+        assert_eq!(answers(Query::Exactly(1)), "y");
+        assert_eq!(answers(Query::Odd), "xy");
+        assert_eq!(
+            answers(Query::Difference(
+                Box::new(Query::AtLeast(2)),
+                Box::new(Query::Everyone)
+            )),
+            "+Z"
+        );
+        assert_eq!(
+            answers(Query::SymmetricDifference(
+                Box::new(Query::Odd),
+                Box::new(Query::Exactly(2))
+            )),
+            "+Zxy"
+        );
+
+        assert_eq!(
+            answers(Query::parse("(odd ^ exactly 2) - (anyone & at-least 3)").unwrap()),
+            "+Zy"
+        );
+        assert_eq!(answers(Query::parse("everyone | exactly 1").unwrap()), "xy");
+        assert_eq!(
+            Query::parse("at-least").unwrap_err(),
+            "at-least needs a number"
+        );
+        assert_eq!(Query::parse("(odd").unwrap_err(), "missing )");
+        assert_eq!(Query::parse("odd odd").unwrap_err(), "unexpected odd");
+
+        assert_eq!(gs.count(&Query::Everyone), 2);
+        assert_eq!(gs.count(&Query::Exactly(2)), 2);
+    }
+}
@@ -0,0 +1,430 @@
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::{BitAnd, BitOr, BitXor, Sub};
+
+/// The operations shared by the inline and heap bit sets. Bit `n` is bit `n % 64` of word
+/// `n / 64`.
+pub trait BitSet: Clone {
+    fn words(&self) -> &[u64];
+    fn words_mut(&mut self) -> &mut [u64];
+    /// Makes room for at least `words` words, which the inline set can't do past its size.
+    fn reserve_words(&mut self, words: usize);
+
+    fn contains(&self, bit: usize) -> bool {
+        self.words()
+            .get(bit / 64)
+            .map(|word| (word >> (bit % 64)) & 1 == 1)
+            .unwrap_or(false)
+    }
+
+    fn insert(&mut self, bit: usize) {
+        self.reserve_words(bit / 64 + 1);
+        self.words_mut()[bit / 64] |= 1 << (bit % 64);
+    }
+
+    fn remove(&mut self, bit: usize) {
+        if let Some(word) = self.words_mut().get_mut(bit / 64) {
+            *word &= !(1 << (bit % 64));
+        }
+    }
+
+    fn clear(&mut self) {
+        for word in self.words_mut().iter_mut() {
+            *word = 0;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.words().iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words().iter().all(|w| *w == 0)
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.iter().next()
+    }
+
+    fn iter(&self) -> Ones<'_> {
+        Ones {
+            words: self.words(),
+            index: 0,
+            current: self.words().first().cloned().unwrap_or(0),
+        }
+    }
+
+    fn union_with<B: BitSet>(&mut self, other: &B) {
+        let other = trim(other.words());
+        self.reserve_words(other.len());
+        for (word, other) in self.words_mut().iter_mut().zip(other.iter()) {
+            *word |= *other;
+        }
+    }
+
+    fn intersect_with<B: BitSet>(&mut self, other: &B) {
+        let other = other.words();
+        for (i, word) in self.words_mut().iter_mut().enumerate() {
+            *word &= other.get(i).cloned().unwrap_or(0);
+        }
+    }
+
+    fn difference_with<B: BitSet>(&mut self, other: &B) {
+        for (word, other) in self.words_mut().iter_mut().zip(other.words().iter()) {
+            *word &= !*other;
+        }
+    }
+
+    fn symmetric_difference_with<B: BitSet>(&mut self, other: &B) {
+        let other = trim(other.words());
+        self.reserve_words(other.len());
+        for (word, other) in self.words_mut().iter_mut().zip(other.iter()) {
+            *word ^= *other;
+        }
+    }
+
+    fn is_subset<B: BitSet>(&self, other: &B) -> bool {
+        let other = other.words();
+        self.words()
+            .iter()
+            .enumerate()
+            .all(|(i, word)| word & !other.get(i).cloned().unwrap_or(0) == 0)
+    }
+
+    fn is_disjoint<B: BitSet>(&self, other: &B) -> bool {
+        self.words()
+            .iter()
+            .zip(other.words().iter())
+            .all(|(a, b)| a & b == 0)
+    }
+
+    /// Every subset of the set, from the full set down to the empty one.
+    fn subsets(&self) -> Subsets<Self> {
+        Subsets {
+            mask: self.clone(),
+            current: Some(self.clone()),
+        }
+    }
+}
+
+/// The words up to the last one with a bit set.
+fn trim(words: &[u64]) -> &[u64] {
+    let len = words
+        .iter()
+        .rposition(|w| *w != 0)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+
+    &words[..len]
+}
+
+pub struct Ones<'a> {
+    words: &'a [u64],
+    index: usize,
+    current: u64,
+}
+
+impl<'a> Iterator for Ones<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.index += 1;
+            if self.index >= self.words.len() {
+                return None;
+            }
+            self.current = self.words[self.index];
+        }
+
+        let bit = self.current.trailing_zeros() as usize;
+        self.current &= self.current - 1;
+
+        Some(self.index * 64 + bit)
+    }
+}
+
+pub struct Subsets<S> {
+    mask: S,
+    current: Option<S>,
+}
+
+impl<S: BitSet> Iterator for Subsets<S> {
+    type Item = S;
+
+    fn next(&mut self) -> Option<S> {
+        let current = self.current.take()?;
+        if current.is_empty() {
+            return Some(current);
+        }
+
+        // (current - 1) & mask, with the borrow carried across the words.
+        let mut next = current.clone();
+        for word in next.words_mut().iter_mut() {
+            let borrowed = *word == 0;
+            *word = word.wrapping_sub(1);
+            if !borrowed {
+                break;
+            }
+        }
+        next.intersect_with(&self.mask);
+        self.current = Some(next);
+
+        Some(current)
+    }
+}
+
+/// A bit set of a fixed number of words that lives on the stack.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct InlineBitSet<const N: usize> {
+    words: [u64; N],
+}
+
+impl<const N: usize> InlineBitSet<N> {
+    pub fn new() -> InlineBitSet<N> {
+        InlineBitSet { words: [0; N] }
+    }
+
+    pub fn from_words(words: [u64; N]) -> InlineBitSet<N> {
+        InlineBitSet { words }
+    }
+}
+
+impl<const N: usize> Default for InlineBitSet<N> {
+    fn default() -> Self {
+        InlineBitSet::new()
+    }
+}
+
+impl<const N: usize> BitSet for InlineBitSet<N> {
+    fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    fn words_mut(&mut self) -> &mut [u64] {
+        &mut self.words
+    }
+
+    fn reserve_words(&mut self, words: usize) {
+        assert!(words <= N, "bit set of {} bits is too small", N * 64);
+    }
+}
+
+/// A bit set that grows to fit whatever is inserted into it.
+#[derive(Clone, Debug, Default)]
+pub struct HeapBitSet {
+    words: Vec<u64>,
+}
+
+impl HeapBitSet {
+    pub fn new() -> HeapBitSet {
+        HeapBitSet { words: Vec::new() }
+    }
+
+    pub fn with_capacity(bits: usize) -> HeapBitSet {
+        HeapBitSet {
+            words: vec![0; bits.div_ceil(64)],
+        }
+    }
+
+    /// A set with every bit below `bits`.
+    pub fn full(bits: usize) -> HeapBitSet {
+        let mut set = HeapBitSet::with_capacity(bits);
+        for word in set.words.iter_mut() {
+            *word = u64::MAX;
+        }
+        if !bits.is_multiple_of(64) {
+            *set.words.last_mut().unwrap() = (1 << (bits % 64)) - 1;
+        }
+
+        set
+    }
+
+    pub fn from_words(words: Vec<u64>) -> HeapBitSet {
+        HeapBitSet { words }
+    }
+
+    fn trimmed(&self) -> &[u64] {
+        trim(&self.words)
+    }
+}
+
+impl BitSet for HeapBitSet {
+    fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    fn words_mut(&mut self) -> &mut [u64] {
+        &mut self.words
+    }
+
+    fn reserve_words(&mut self, words: usize) {
+        if self.words.len() < words {
+            self.words.resize(words, 0);
+        }
+    }
+}
+
+impl PartialEq for HeapBitSet {
+    fn eq(&self, other: &HeapBitSet) -> bool {
+        self.trimmed() == other.trimmed()
+    }
+}
+
+impl Eq for HeapBitSet {}
+
+impl Hash for HeapBitSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.trimmed().hash(state);
+    }
+}
+
+impl FromIterator<usize> for HeapBitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> HeapBitSet {
+        let mut set = HeapBitSet::new();
+        for bit in iter {
+            set.insert(bit);
+        }
+
+        set
+    }
+}
+
+impl<const N: usize> FromIterator<usize> for InlineBitSet<N> {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> InlineBitSet<N> {
+        let mut set = InlineBitSet::new();
+        for bit in iter {
+            set.insert(bit);
+        }
+
+        set
+    }
+}
+
+macro_rules! impl_bitset_ops {
+    ($(impl[$($g:tt)*] $t:ty;)*) => {
+        $(
+            impl<$($g)*> BitAnd for &$t {
+                type Output = $t;
+
+                fn bitand(self, other: &$t) -> $t {
+                    let mut set = self.clone();
+                    set.intersect_with(other);
+                    set
+                }
+            }
+
+            impl<$($g)*> BitOr for &$t {
+                type Output = $t;
+
+                fn bitor(self, other: &$t) -> $t {
+                    let mut set = self.clone();
+                    set.union_with(other);
+                    set
+                }
+            }
+
+            impl<$($g)*> BitXor for &$t {
+                type Output = $t;
+
+                fn bitxor(self, other: &$t) -> $t {
+                    let mut set = self.clone();
+                    set.symmetric_difference_with(other);
+                    set
+                }
+            }
+
+            impl<$($g)*> Sub for &$t {
+                type Output = $t;
+
+                fn sub(self, other: &$t) -> $t {
+                    let mut set = self.clone();
+                    set.difference_with(other);
+                    set
+                }
+            }
+        )*
+    };
+}
+
+impl_bitset_ops! {
+    impl[const N: usize] InlineBitSet<N>;
+    impl[] HeapBitSet;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic_ops() {
+        let mut set = HeapBitSet::new();
+        set.insert(3);
+        set.insert(70);
+        set.insert(200);
+        set.remove(70);
+
+        assert!(set.contains(3));
+        assert!(!set.contains(70));
+        assert!(!set.contains(5000));
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 200]);
+
+        let other: HeapBitSet = vec![3, 4].into_iter().collect();
+        assert_eq!(&set & &other, vec![3].into_iter().collect());
+        assert_eq!(&set | &other, vec![3, 4, 200].into_iter().collect());
+        assert_eq!(&set ^ &other, vec![4, 200].into_iter().collect());
+        assert_eq!(&set - &other, vec![200].into_iter().collect());
+        assert!((&set & &other).is_subset(&other));
+        assert!(!set.is_disjoint(&other));
+        assert_eq!(HeapBitSet::full(66).len(), 66);
+    }
+
+    #[test]
+    fn test_inline() {
+        let a: InlineBitSet<2> = vec![1, 64, 127].into_iter().collect();
+        let b: InlineBitSet<2> = vec![64].into_iter().collect();
+
+        assert_eq!((&a - &b).iter().collect::<Vec<_>>(), vec![1, 127]);
+        assert!(b.is_subset(&a));
+        assert_eq!(a.first(), Some(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_inline_overflow() {
+        InlineBitSet::<1>::new().insert(64);
+    }
+
+    #[test]
+    fn test_inline_with_heap() {
+        // Words past the inline capacity are fine as long as they're empty.
+        let mut heap: HeapBitSet = vec![3, 100].into_iter().collect();
+        heap.remove(100);
+
+        let mut set: InlineBitSet<1> = vec![1, 3].into_iter().collect();
+        set.union_with(&heap);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![1, 3]);
+        set.symmetric_difference_with(&heap);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_inline_union_overflow() {
+        let heap: HeapBitSet = vec![100].into_iter().collect();
+        InlineBitSet::<1>::new().union_with(&heap);
+    }
+
+    #[test]
+    fn test_subsets() {
+        let set: InlineBitSet<1> = vec![0, 2].into_iter().collect();
+        let subsets: Vec<Vec<usize>> = set.subsets().map(|s| s.iter().collect()).collect();
+        assert_eq!(subsets, vec![vec![0, 2], vec![2], vec![0], vec![]]);
+
+        let wide: HeapBitSet = vec![1, 63, 64, 130].into_iter().collect();
+        let subsets: Vec<HeapBitSet> = wide.subsets().collect();
+        assert_eq!(subsets.len(), 16);
+        assert!(subsets.iter().all(|s| s.is_subset(&wide)));
+        assert_eq!(subsets[1], vec![63, 64, 130].into_iter().collect());
+    }
+}
@@ -0,0 +1,404 @@
+use std::cmp::min;
+use std::fmt::{Display, Write};
+
+/// How an instruction passes control to the next one, with jump offsets relative to its pc.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Flow {
+    Next,
+    Jump(isize),
+    Branch(isize),
+}
+
+pub trait ControlFlow {
+    fn flow(&self) -> Flow;
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Target {
+    Block(usize),
+    End,
+    OutOfRange,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    /// The fall-through target comes first for branches, followed by the taken one.
+    pub successors: Vec<Target>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cfg {
+    blocks: Vec<BasicBlock>,
+    block_of: Vec<usize>,
+}
+
+impl Cfg {
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block_at(&self, pc: usize) -> usize {
+        self.block_of[pc]
+    }
+
+    /// Blocks that can be reached from the first instruction.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        if self.blocks.is_empty() {
+            return reachable;
+        }
+
+        let mut stack = vec![0];
+        reachable[0] = true;
+        while let Some(block) = stack.pop() {
+            for target in self.blocks[block].successors.iter() {
+                if let Target::Block(next) = *target {
+                    if !reachable[next] {
+                        reachable[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+
+        reachable
+    }
+
+    /// Whether the program can run off its end when started at each instruction.
+    pub fn terminating(&self) -> Vec<bool> {
+        let mut sources = vec![Vec::new(); self.blocks.len()];
+        let mut stack = Vec::new();
+        let mut terminating_blocks = vec![false; self.blocks.len()];
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            for target in block.successors.iter() {
+                match *target {
+                    Target::Block(next) => sources[next].push(index),
+                    Target::End => {
+                        if !terminating_blocks[index] {
+                            terminating_blocks[index] = true;
+                            stack.push(index);
+                        }
+                    }
+                    Target::OutOfRange => {}
+                }
+            }
+        }
+
+        while let Some(block) = stack.pop() {
+            for source in sources[block].iter() {
+                if !terminating_blocks[*source] {
+                    terminating_blocks[*source] = true;
+                    stack.push(*source);
+                }
+            }
+        }
+
+        self.block_of
+            .iter()
+            .map(|block| terminating_blocks[*block])
+            .collect()
+    }
+
+    /// The strongly connected components that contain a cycle, as sorted lists of blocks.
+    pub fn loops(&self) -> Vec<Vec<usize>> {
+        const UNVISITED: usize = usize::MAX;
+
+        let n = self.blocks.len();
+        let adjacency: Vec<Vec<usize>> = self
+            .blocks
+            .iter()
+            .map(|block| {
+                block
+                    .successors
+                    .iter()
+                    .filter_map(|target| match *target {
+                        Target::Block(next) => Some(next),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut index = vec![UNVISITED; n];
+        let mut low = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = Vec::new();
+        let mut calls = Vec::new();
+        let mut next_index = 0;
+        let mut loops = Vec::new();
+
+        for root in 0..n {
+            if index[root] != UNVISITED {
+                continue;
+            }
+
+            index[root] = next_index;
+            low[root] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            calls.push((root, 0));
+
+            while let Some((v, i)) = calls.pop() {
+                if i < adjacency[v].len() {
+                    calls.push((v, i + 1));
+
+                    let w = adjacency[v][i];
+                    if index[w] == UNVISITED {
+                        index[w] = next_index;
+                        low[w] = next_index;
+                        next_index += 1;
+                        stack.push(w);
+                        on_stack[w] = true;
+                        calls.push((w, 0));
+                    } else if on_stack[w] {
+                        low[v] = min(low[v], index[w]);
+                    }
+
+                    continue;
+                }
+
+                if low[v] == index[v] {
+                    let mut component = Vec::new();
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+
+                    if component.len() > 1 || adjacency[v].contains(&v) {
+                        component.sort_unstable();
+                        loops.push(component);
+                    }
+                }
+                if let Some(&(u, _)) = calls.last() {
+                    low[u] = min(low[u], low[v]);
+                }
+            }
+        }
+
+        loops.sort_unstable();
+        loops
+    }
+
+    /// The loops that run from the target of a backward jump to the jump itself, as pc
+    /// ranges that nothing inside jumps out of except to the instruction after the range.
+    /// Inner loops come before the loops around them.
+    pub fn closed_loops(&self) -> Vec<(usize, usize)> {
+        let mut loops = Vec::new();
+        for block in self.blocks.iter() {
+            for target in block.successors.iter() {
+                if let Target::Block(header) = *target {
+                    let start = self.blocks[header].start;
+                    if start <= block.start && self.is_closed(start, block.end) {
+                        loops.push((start, block.end));
+                    }
+                }
+            }
+        }
+
+        loops.sort_unstable_by_key(|&(start, end)| (end - start, start));
+        loops
+    }
+
+    fn is_closed(&self, start: usize, end: usize) -> bool {
+        let len = self.block_of.len();
+        self.blocks[self.block_of[start]..=self.block_of[end - 1]]
+            .iter()
+            .flat_map(|block| block.successors.iter())
+            .all(|target| match *target {
+                Target::Block(next) => (start..=end).contains(&self.blocks[next].start),
+                Target::End => end == len,
+                Target::OutOfRange => false,
+            })
+    }
+
+    pub fn to_dot<I: Display>(&self, instructions: &[I]) -> String {
+        let mut dot = String::with_capacity(self.blocks.len() * 64);
+        let mut has_end = false;
+        let mut has_out = false;
+
+        dot.push_str("digraph cfg {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for (i, instruction) in instructions[block.start..block.end].iter().enumerate() {
+                write!(label, "{}: {}\\l", block.start + i, instruction).unwrap();
+            }
+            writeln!(
+                dot,
+                "    b{} [label=\"{}\"];",
+                index,
+                label.replace('"', "\\\"")
+            )
+            .unwrap();
+
+            for (i, target) in block.successors.iter().enumerate() {
+                let name = match *target {
+                    Target::Block(next) => format!("b{}", next),
+                    Target::End => {
+                        has_end = true;
+                        "end".to_owned()
+                    }
+                    Target::OutOfRange => {
+                        has_out = true;
+                        "out".to_owned()
+                    }
+                };
+
+                if block.successors.len() > 1 {
+                    let label = if i == 0 { "fall" } else { "taken" };
+                    writeln!(dot, "    b{} -> {} [label=\"{}\"];", index, name, label).unwrap();
+                } else {
+                    writeln!(dot, "    b{} -> {};", index, name).unwrap();
+                }
+            }
+        }
+
+        if has_end {
+            dot.push_str("    end [shape=doublecircle];\n");
+        }
+        if has_out {
+            dot.push_str("    out [shape=octagon, label=\"out of range\"];\n");
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    pub fn build<I: ControlFlow>(instructions: &[I]) -> Cfg {
+        let len = instructions.len();
+        let target_of = |pc: usize, offset: isize| -> Option<usize> {
+            let target = pc as isize + offset;
+            if target < 0 || target as usize > len {
+                None
+            } else {
+                Some(target as usize)
+            }
+        };
+
+        let mut leaders = vec![false; len + 1];
+        if len > 0 {
+            leaders[0] = true;
+        }
+        for (pc, instruction) in instructions.iter().enumerate() {
+            match instruction.flow() {
+                Flow::Next => {}
+                Flow::Jump(offset) | Flow::Branch(offset) => {
+                    leaders[pc + 1] = true;
+                    if let Some(target) = target_of(pc, offset) {
+                        leaders[target] = true;
+                    }
+                }
+            }
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        let mut block_of = Vec::with_capacity(len);
+        for (pc, leader) in leaders[..len].iter().enumerate() {
+            if *leader {
+                if let Some(last) = blocks.last_mut() {
+                    last.end = pc;
+                }
+                blocks.push(BasicBlock {
+                    start: pc,
+                    end: len,
+                    successors: Vec::new(),
+                });
+            }
+            block_of.push(blocks.len() - 1);
+        }
+
+        let resolve = |target: Option<usize>| match target {
+            Some(pc) if pc == len => Target::End,
+            Some(pc) => Target::Block(block_of[pc]),
+            None => Target::OutOfRange,
+        };
+
+        for block in blocks.iter_mut() {
+            let pc = block.end - 1;
+            block.successors = match instructions[pc].flow() {
+                Flow::Next => vec![resolve(Some(pc + 1))],
+                Flow::Jump(offset) => vec![resolve(target_of(pc, offset))],
+                Flow::Branch(offset) => {
+                    let fall = resolve(Some(pc + 1));
+                    let taken = resolve(target_of(pc, offset));
+                    if fall == taken {
+                        vec![fall]
+                    } else {
+                        vec![fall, taken]
+                    }
+                }
+            };
+        }
+
+        Cfg { blocks, block_of }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Program;
+
+    const EXAMPLE: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6
+";
+
+    #[test]
+    fn test_blocks() {
+        let program = Program::parse(EXAMPLE);
+        let cfg = Cfg::build(program.instructions());
+
+        let ranges: Vec<(usize, usize)> = cfg.blocks().iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(ranges, vec![(0, 1), (1, 3), (3, 5), (5, 6), (6, 8), (8, 9)]);
+        assert_eq!(cfg.blocks()[1].successors, vec![Target::Block(4)]);
+        assert_eq!(cfg.blocks()[5].successors, vec![Target::End]);
+        assert_eq!(cfg.block_at(7), 4);
+        assert_eq!(cfg.reachable(), vec![true, true, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_analysis() {
+        let program = Program::parse(EXAMPLE);
+        let cfg = Cfg::build(program.instructions());
+
+        assert_eq!(cfg.loops(), vec![vec![1, 2, 4]]);
+        assert_eq!(cfg.closed_loops(), vec![]);
+        assert_eq!(
+            cfg.terminating(),
+            vec![false, false, false, false, false, false, false, false, true]
+        );
+
+        let program = Program::parse("nop +0\nacc +1\njmp -1\nacc +2\n");
+        assert_eq!(
+            Cfg::build(program.instructions()).closed_loops(),
+            vec![(1, 3)]
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let program = Program::parse("acc +1\njmp +2\njmp -5\nacc +2\n");
+        let dot = Cfg::build(program.instructions()).to_dot(program.instructions());
+
+        assert!(dot.contains("b0 [label=\"0: acc +1\\l1: jmp +2\\l\"];"));
+        assert!(dot.contains("b0 -> b2;"));
+        assert!(dot.contains("b1 -> out;"));
+        assert!(dot.contains("b2 -> end;"));
+        assert!(dot.contains("end [shape=doublecircle];"));
+    }
+}
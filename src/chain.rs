@@ -0,0 +1,247 @@
+use num::traits::CheckedAdd;
+use num::{BigUint, One, Zero};
+use std::collections::BTreeMap;
+
+/// The adapters sorted by joltage, between the outlet at 0 and the device, which is rated
+/// the largest allowed gap above the highest adapter.
+#[derive(Debug)]
+pub struct Chain {
+    joltages: Vec<u64>,
+    gaps: Vec<u64>,
+}
+
+impl Chain {
+    pub fn new(adapters: &[u64], gaps: &[u64]) -> Chain {
+        let mut gaps = gaps.to_vec();
+        gaps.sort_unstable();
+        gaps.dedup();
+
+        let mut joltages = Vec::with_capacity(adapters.len() + 2);
+        joltages.push(0);
+        joltages.extend_from_slice(adapters);
+        joltages.sort_unstable();
+
+        let highest = *joltages.last().unwrap();
+        joltages.push(highest + gaps.last().cloned().unwrap_or(0));
+
+        Chain { joltages, gaps }
+    }
+
+    fn allows(&self, from: usize, to: usize) -> bool {
+        self.gaps
+            .binary_search(&(self.joltages[to] - self.joltages[from]))
+            .is_ok()
+    }
+
+    /// The positions that can follow `from`, in order.
+    fn successors(&self, from: usize) -> impl Iterator<Item = usize> + '_ {
+        let largest = self.gaps.last().cloned().unwrap_or(0);
+
+        (from + 1..self.joltages.len())
+            .take_while(move |to| self.joltages[*to] - self.joltages[from] <= largest)
+            .filter(move |to| self.allows(from, *to))
+    }
+
+    /// How often each gap occurs when every adapter is used, or `None` if some gap between
+    /// neighbours isn't allowed.
+    pub fn histogram(&self) -> Option<BTreeMap<u64, usize>> {
+        let mut histogram = BTreeMap::new();
+        for i in 1..self.joltages.len() {
+            if !self.allows(i - 1, i) {
+                return None;
+            }
+
+            *histogram
+                .entry(self.joltages[i] - self.joltages[i - 1])
+                .or_insert(0) += 1;
+        }
+
+        Some(histogram)
+    }
+
+    /// The number of arrangements from each position to the device, or `None` if a count
+    /// doesn't fit in `T`.
+    pub fn counts_to_end<T: Clone + Zero + One + CheckedAdd>(&self) -> Option<Vec<T>> {
+        let len = self.joltages.len();
+        let mut counts = vec![T::zero(); len];
+        counts[len - 1] = T::one();
+
+        for i in (0..len - 1).rev() {
+            let mut count = T::zero();
+            for j in self.successors(i) {
+                count = count.checked_add(&counts[j])?;
+            }
+            counts[i] = count;
+        }
+
+        Some(counts)
+    }
+
+    /// `counts_to_end` with counts that always fit.
+    fn big_counts_to_end(&self) -> Vec<BigUint> {
+        // `BigUint::checked_add` only fails when memory runs out, and then it panics anyway.
+        self.counts_to_end::<BigUint>()
+            .expect("big counts can't overflow")
+    }
+
+    /// The number of arrangements.
+    pub fn count(&self) -> BigUint {
+        self.big_counts_to_end().swap_remove(0)
+    }
+
+    /// Every arrangement as the joltages of the adapters it uses, in the same order as
+    /// `nth` counts them.
+    pub fn arrangements(&self) -> Arrangements<'_> {
+        let counts = self.big_counts_to_end();
+        let reaches_end: Vec<bool> = counts.iter().map(|c| !c.is_zero()).collect();
+
+        let mut arrangements = Arrangements {
+            chain: self,
+            reaches_end,
+            path: vec![0],
+            done: false,
+        };
+        arrangements.done = !arrangements.complete();
+
+        arrangements
+    }
+
+    /// The arrangement at `index` in the order `arrangements` lists them, if there are that
+    /// many.
+    pub fn nth(&self, index: &BigUint) -> Option<Vec<u64>> {
+        let counts = self.big_counts_to_end();
+        if *index >= counts[0] {
+            return None;
+        }
+
+        let mut index = index.clone();
+        let mut path = Vec::new();
+        let mut pos = 0;
+        while pos < self.joltages.len() - 1 {
+            for next in self.successors(pos) {
+                if index < counts[next] {
+                    pos = next;
+                    break;
+                }
+                index -= &counts[next];
+            }
+            path.push(self.joltages[pos]);
+        }
+
+        path.pop();
+        Some(path)
+    }
+
+    /// Picks an arrangement uniformly at random, drawing 64 random bits at a time from
+    /// `random`. Returns `None` if there are no arrangements.
+    pub fn sample(&self, mut random: impl FnMut() -> u64) -> Option<Vec<u64>> {
+        let count = self.count();
+        if count.is_zero() {
+            return None;
+        }
+
+        // An extra 64 bits keeps the bias from taking the remainder negligible.
+        let words = count.bits() / 64 + 2;
+        let value = (0..words).fold(BigUint::zero(), |acc, _| (acc << 64) + random());
+
+        self.nth(&(value % count))
+    }
+}
+
+pub struct Arrangements<'a> {
+    chain: &'a Chain,
+    reaches_end: Vec<bool>,
+    /// The positions in the current arrangement, from the outlet to the device.
+    path: Vec<usize>,
+    done: bool,
+}
+
+impl<'a> Arrangements<'a> {
+    /// Extends the path to the device with the lowest successors that can still get there.
+    fn complete(&mut self) -> bool {
+        let end = self.chain.joltages.len() - 1;
+        if !self.reaches_end[0] {
+            return false;
+        }
+
+        while let Some(&last) = self.path.last() {
+            if last == end {
+                break;
+            }
+
+            let reaches_end = &self.reaches_end;
+            let next = self.chain.successors(last).find(|to| reaches_end[*to]);
+            self.path.push(next.unwrap());
+        }
+
+        true
+    }
+
+    /// Replaces the last position that has a higher alternative, and completes the path
+    /// after it.
+    fn advance(&mut self) -> bool {
+        while self.path.len() > 1 {
+            let last = self.path.pop().unwrap();
+            let prev = *self.path.last().unwrap();
+
+            let reaches_end = &self.reaches_end;
+            let next = self
+                .chain
+                .successors(prev)
+                .find(|to| *to > last && reaches_end[*to]);
+            if let Some(next) = next {
+                self.path.push(next);
+                return self.complete();
+            }
+        }
+
+        false
+    }
+}
+
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<u64>;
+
+    fn next(&mut self) -> Option<Vec<u64>> {
+        if self.done {
+            return None;
+        }
+
+        let joltages = &self.chain.joltages;
+        let adapters = &self.path[1..self.path.len() - 1];
+        let arrangement = adapters.iter().map(|i| joltages[*i]).collect();
+        self.done = !self.advance();
+
+        Some(arrangement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    const GAPS: &[u64] = &[1, 2, 3];
+    const EXAMPLE1: &[u64] = &[1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+
+    #[test]
+    fn test_arrangements() {
+        let chain = Chain::new(EXAMPLE1, GAPS);
+        let all: Vec<Vec<u64>> = chain.arrangements().collect();
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[0], EXAMPLE1.to_vec());
+        assert_eq!(all[7], vec![1, 4, 7, 10, 12, 15, 16, 19]);
+
+        for (i, arrangement) in all.iter().enumerate() {
+            assert_eq!(chain.nth(&BigUint::from(i)).as_ref(), Some(arrangement));
+        }
+        assert_eq!(chain.nth(&BigUint::from(8u32)), None);
+
+        let mut rng = Rng::new(0x2545f4914f6cdd1d);
+        assert!(all.contains(&chain.sample(|| rng.next_u64()).unwrap()));
+
+        let odd = Chain::new(&[2, 4, 5, 7], &[2]);
+        assert_eq!(odd.arrangements().count(), 0);
+        assert_eq!(odd.sample(|| 0), None);
+    }
+}
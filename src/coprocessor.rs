@@ -0,0 +1,1015 @@
+use crate::cfg::{Cfg, ControlFlow, Flow};
+use crate::parsers::parse_i64;
+use std::fmt;
+
+const C_A: u8 = b'a';
+const C_Z: u8 = b'z';
+
+/// An instruction for the coprocessor from 2017 day 23. Arithmetic wraps around like it
+/// does in release builds, so debug builds don't panic on overflow.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Instruction {
+    SetN(usize, i64),   // set RX N
+    SetR(usize, usize), // set RX RY
+    SubN(usize, i64),   // sub RX N
+    SubR(usize, usize), // sub RX RY
+    MulN(usize, i64),   // mul RX N
+    MulR(usize, usize), // mul RX RY
+    Jnz(usize, i64),    // jnz RX N
+    Jnz1(i64),          // jnz 1 N
+}
+
+impl Instruction {
+    pub fn execute(&self, registers: &mut [i64; 26], pc: &mut usize, muls: &mut u64) {
+        match *self {
+            Instruction::SetN(r, n) => {
+                registers[r] = n;
+                *pc += 1;
+            }
+            Instruction::SetR(r1, r2) => {
+                registers[r1] = registers[r2];
+                *pc += 1;
+            }
+            Instruction::SubN(r, n) => {
+                registers[r] = registers[r].wrapping_sub(n);
+                *pc += 1;
+            }
+            Instruction::SubR(r1, r2) => {
+                registers[r1] = registers[r1].wrapping_sub(registers[r2]);
+                *pc += 1;
+            }
+            Instruction::MulN(r, n) => {
+                registers[r] = registers[r].wrapping_mul(n);
+                *pc += 1;
+                *muls += 1;
+            }
+            Instruction::MulR(r1, r2) => {
+                registers[r1] = registers[r1].wrapping_mul(registers[r2]);
+                *pc += 1;
+                *muls += 1;
+            }
+            Instruction::Jnz1(offset) => {
+                *pc = (*pc as i64 + offset) as usize;
+            }
+            Instruction::Jnz(r, offset) => {
+                if registers[r] != 0 {
+                    *pc = (*pc as i64 + offset) as usize;
+                } else {
+                    *pc += 1;
+                }
+            }
+        }
+    }
+}
+
+impl ControlFlow for Instruction {
+    fn flow(&self) -> Flow {
+        match *self {
+            Instruction::Jnz1(offset) => Flow::Jump(offset as isize),
+            Instruction::Jnz(_, offset) => Flow::Branch(offset as isize),
+            _ => Flow::Next,
+        }
+    }
+}
+
+fn reg(r: usize) -> char {
+    (C_A + r as u8) as char
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::SetN(r, n) => write!(f, "set {} {}", reg(r), n),
+            Instruction::SetR(r1, r2) => write!(f, "set {} {}", reg(r1), reg(r2)),
+            Instruction::SubN(r, n) => write!(f, "sub {} {}", reg(r), n),
+            Instruction::SubR(r1, r2) => write!(f, "sub {} {}", reg(r1), reg(r2)),
+            Instruction::MulN(r, n) => write!(f, "mul {} {}", reg(r), n),
+            Instruction::MulR(r1, r2) => write!(f, "mul {} {}", reg(r1), reg(r2)),
+            Instruction::Jnz(r, offset) => write!(f, "jnz {} {}", reg(r), offset),
+            Instruction::Jnz1(offset) => write!(f, "jnz 1 {}", offset),
+        }
+    }
+}
+
+pub fn parse(s: &str) -> Vec<Instruction> {
+    s.lines()
+        .map(|l| {
+            let command = &l[..3];
+            let value = &l[6..];
+            let pfx = l.as_bytes()[4];
+            let is_register = (C_A..=C_Z).contains(&pfx);
+            let register_idx = if is_register { (pfx - C_A) as usize } else { 0 };
+            let pfx2 = l.as_bytes()[6];
+            let is_register2 = (C_A..=C_Z).contains(&pfx2);
+            let register_idx2 = if is_register2 {
+                (pfx2 - C_A) as usize
+            } else {
+                0
+            };
+
+            match command {
+                "set" => {
+                    if is_register2 {
+                        Instruction::SetR(register_idx, register_idx2)
+                    } else {
+                        Instruction::SetN(register_idx, parse_i64(value))
+                    }
+                }
+                "sub" => {
+                    if is_register2 {
+                        Instruction::SubR(register_idx, register_idx2)
+                    } else {
+                        Instruction::SubN(register_idx, parse_i64(value))
+                    }
+                }
+                "mul" => {
+                    if is_register2 {
+                        Instruction::MulR(register_idx, register_idx2)
+                    } else {
+                        Instruction::MulN(register_idx, parse_i64(value))
+                    }
+                }
+                "jnz" => {
+                    if is_register {
+                        Instruction::Jnz(register_idx, parse_i64(value))
+                    } else {
+                        Instruction::Jnz1(parse_i64(value))
+                    }
+                }
+
+                _ => panic!("Unknown op {}", command),
+            }
+        })
+        .collect()
+}
+
+pub struct Program<'a> {
+    instructions: &'a [Instruction],
+    pub registers: [i64; 26],
+    pub pc: usize,
+    pub muls: u64,
+}
+
+impl<'a> Program<'a> {
+    pub fn run(&mut self) -> bool {
+        self.instructions[self.pc].execute(&mut self.registers, &mut self.pc, &mut self.muls);
+
+        self.pc == self.instructions.len()
+    }
+
+    pub fn run_until_end(&mut self) {
+        while !self.run() {}
+    }
+
+    pub fn from(instructions: &[Instruction]) -> Program<'_> {
+        Program {
+            instructions,
+            registers: [0; 26],
+            muls: 0,
+            pc: 0,
+        }
+    }
+}
+
+/// An instruction or recognized idiom of the optimized program. Every op replaces the
+/// instruction at the same pc, so jump offsets stay valid and the original instructions
+/// remain in place for jumps into the middle of an idiom. The loop idioms sit at the first
+/// instruction of a loop found in the control-flow graph, with `step` being what the loop
+/// adds to its counter on each pass.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Op {
+    Base(Instruction),
+    /// `set g x; sub g y; jnz g N`
+    SubJnz {
+        g: usize,
+        x: usize,
+        y: usize,
+        target: usize,
+    },
+    /// A loop of `sub` instructions that counts `counter` down to zero, which multiplies
+    /// everything it subtracts by the number of passes.
+    MulLoop {
+        counter: usize,
+        step: i64,
+        end: usize,
+    },
+    /// A loop over `e` up to `b` that clears `f` if `d * e == b`.
+    FindFactor {
+        b: usize,
+        d: usize,
+        e: usize,
+        f: usize,
+        g: usize,
+        step: i64,
+        end: usize,
+    },
+    /// A loop over `d` up to `b` around a `FindFactor` loop over `e` from `e0` up to `b`,
+    /// which clears `f` if `d * e == b`. That is a composite check when both start at 2.
+    CheckComposite {
+        b: usize,
+        d: usize,
+        e: usize,
+        f: usize,
+        g: usize,
+        e0: i64,
+        d_step: i64,
+        e_step: i64,
+        end: usize,
+    },
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Op::Base(instruction) => write!(f, "{}", instruction),
+            Op::SubJnz { g, x, y, target } => write!(
+                f,
+                "{} = {} - {}; if {} != 0 goto {}",
+                reg(g),
+                reg(x),
+                reg(y),
+                reg(g),
+                target
+            ),
+            Op::MulLoop { counter, step, end } => write!(
+                f,
+                "repeat the loop {c} / {} times at once; {c} = 0; goto {}",
+                -step,
+                end,
+                c = reg(counter),
+            ),
+            Op::FindFactor {
+                b,
+                d,
+                e,
+                f: rf,
+                g,
+                step,
+                end,
+            } => write!(
+                f,
+                "if {d} * k == {b} for k from {e} by {} until {b} {{ {f} = 0 }}; {e} = {b}; {g} = 0; goto {}",
+                step,
+                end,
+                b = reg(b),
+                d = reg(d),
+                e = reg(e),
+                f = reg(rf),
+                g = reg(g),
+            ),
+            Op::CheckComposite {
+                b,
+                d,
+                e,
+                f: rf,
+                g,
+                e0,
+                d_step,
+                e_step,
+                end,
+            } => write!(
+                f,
+                "if i * j == {b} for i from {d} by {} until {b}, j from {} by {} until {b} {{ {f} = 0 }}; {d} = {b}; {e} = {b}; {g} = 0; goto {}",
+                d_step,
+                e0,
+                e_step,
+                end,
+                b = reg(b),
+                d = reg(d),
+                e = reg(e),
+                f = reg(rf),
+                g = reg(g),
+            ),
+        }
+    }
+}
+
+fn distinct(registers: &[usize]) -> bool {
+    registers
+        .iter()
+        .enumerate()
+        .all(|(i, r)| !registers[i + 1..].contains(r))
+}
+
+/// The register an instruction writes to, if any.
+fn written(instruction: &Instruction) -> Option<usize> {
+    match *instruction {
+        Instruction::SetN(r, _)
+        | Instruction::SetR(r, _)
+        | Instruction::SubN(r, _)
+        | Instruction::SubR(r, _)
+        | Instruction::MulN(r, _)
+        | Instruction::MulR(r, _) => Some(r),
+        Instruction::Jnz(..) | Instruction::Jnz1(_) => None,
+    }
+}
+
+/// Matches a loop whose body only subtracts constants and registers it leaves alone, and
+/// which ends by testing a counter that the body steps by a constant.
+fn match_mul_loop(code: &[Instruction], end: usize) -> Option<Op> {
+    use Instruction::*;
+
+    let (counter, body) = match code.split_last()? {
+        (Jnz(counter, _), body) => (*counter, body),
+        _ => return None,
+    };
+
+    let mut step = None;
+    for instruction in body.iter() {
+        match *instruction {
+            SubN(r, n) if r == counter && step.is_none() => step = Some(n.checked_neg()?),
+            SubN(r, _) if r != counter => {}
+            SubR(r, y) if r != counter && y != counter => {
+                if body.iter().any(|i| written(i) == Some(y)) {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    Some(Op::MulLoop {
+        counter,
+        step: step?,
+        end,
+    })
+}
+
+fn match_find_factor(code: &[Instruction], end: usize) -> Option<Op> {
+    use Instruction::*;
+
+    match *code {
+        [SetR(g, x), MulR(g2, y), SubR(g3, b), Jnz(g4, 2), SetN(f, 0), SubN(e, n), SetR(g5, p), SubR(g6, q), Jnz(g7, _)]
+            if [g2, g3, g4, g5, g6, g7].iter().all(|r| *r == g)
+                && (x == e || y == e)
+                && ((p, q) == (e, b) || (p, q) == (b, e)) =>
+        {
+            let d = if x == e { y } else { x };
+            if !distinct(&[b, d, e, f, g]) {
+                return None;
+            }
+
+            Some(Op::FindFactor {
+                b,
+                d,
+                e,
+                f,
+                g,
+                step: n.checked_neg()?,
+                end,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Matches a loop that resets `e` and then runs an already matched `FindFactor` loop.
+fn match_check_composite(code: &[Instruction], inner: Op, end: usize) -> Option<Op> {
+    use Instruction::*;
+
+    let (b, d, e, f, g, e_step) = match inner {
+        Op::FindFactor {
+            b,
+            d,
+            e,
+            f,
+            g,
+            step,
+            end: inner_end,
+        } if inner_end == end - 4 => (b, d, e, f, g, step),
+        _ => return None,
+    };
+
+    match *code {
+        [SetN(e2, e0), .., SubN(d2, n), SetR(g2, p), SubR(g3, q), Jnz(g4, _)]
+            if code.len() == 14
+                && e2 == e
+                && d2 == d
+                && [g2, g3, g4].iter().all(|r| *r == g)
+                && ((p, q) == (d, b) || (p, q) == (b, d)) =>
+        {
+            Some(Op::CheckComposite {
+                b,
+                d,
+                e,
+                f,
+                g,
+                e0,
+                d_step: n.checked_neg()?,
+                e_step,
+                end,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn match_sub_jnz(code: &[Instruction], pc: usize) -> Option<Op> {
+    use Instruction::*;
+
+    match *code {
+        [SetR(g, x), SubR(g2, y), Jnz(g3, offset), ..] if g2 == g && g3 == g && y != g => {
+            Some(Op::SubJnz {
+                g,
+                x,
+                y,
+                target: (pc as i64 + 2 + offset) as usize,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The factor loops only take shortcuts for values within this bound, so that none of the
+/// products they stand for could wrap around.
+const FACTOR_LIMIT: i64 = 1 << 30;
+
+/// How many passes a loop makes that adds `step` to a value starting at `from`, and stops
+/// once it equals `to`. `None` if it would wrap around first.
+fn passes(from: i64, to: i64, step: i64) -> Option<i64> {
+    let diff = to.checked_sub(from)?;
+    if step == 0 || diff.checked_rem(step)? != 0 {
+        return None;
+    }
+
+    Some(diff / step).filter(|n| *n > 0)
+}
+
+/// Whether `x` is among the `n` values starting at `from` and going up by `step`.
+fn in_passes(x: i64, from: i64, step: i64, n: i64) -> bool {
+    let diff = x - from;
+    diff % step == 0 && (0..n).contains(&(diff / step))
+}
+
+/// Runs a `MulLoop` at once. Returns the number of multiplications, or `None` if the
+/// counter wouldn't reach zero.
+fn mul_loop(
+    registers: &mut [i64; 26],
+    body: &[Instruction],
+    counter: usize,
+    step: i64,
+) -> Option<u64> {
+    let n = passes(registers[counter], 0, step)?;
+    for instruction in body.iter() {
+        match *instruction {
+            Instruction::SubN(r, m) if r != counter => {
+                registers[r] = registers[r].wrapping_sub(m.wrapping_mul(n));
+            }
+            Instruction::SubR(r, y) => {
+                registers[r] = registers[r].wrapping_sub(registers[y].wrapping_mul(n));
+            }
+            _ => {}
+        }
+    }
+    registers[counter] = 0;
+
+    Some(0)
+}
+
+/// Runs a `FindFactor` loop at once, with the same result and number of multiplications.
+fn find_factor(
+    registers: &mut [i64; 26],
+    (b, d, e, f, g): (usize, usize, usize, usize, usize),
+    step: i64,
+) -> Option<u64> {
+    let (vb, vd, ve) = (registers[b], registers[d], registers[e]);
+    if [vb, vd, ve].iter().any(|v| v.abs() > FACTOR_LIMIT) {
+        return None;
+    }
+
+    let n = passes(ve, vb, step)?;
+    let found = if vd == 0 {
+        vb == 0
+    } else {
+        vb % vd == 0 && in_passes(vb / vd, ve, step, n)
+    };
+    if found {
+        registers[f] = 0;
+    }
+    registers[e] = vb;
+    registers[g] = 0;
+
+    Some(n as u64)
+}
+
+/// Runs a `CheckComposite` loop at once, with the same result and number of
+/// multiplications.
+fn check_composite(
+    registers: &mut [i64; 26],
+    (b, d, e, f, g): (usize, usize, usize, usize, usize),
+    e0: i64,
+    d_step: i64,
+    e_step: i64,
+) -> Option<u64> {
+    let (vb, vd) = (registers[b], registers[d]);
+    if [vb, vd, e0].iter().any(|v| v.abs() > FACTOR_LIMIT) {
+        return None;
+    }
+
+    let n_d = passes(vd, vb, d_step)?;
+    let n_e = passes(e0, vb, e_step)?;
+    let found = if vb == 0 {
+        in_passes(0, vd, d_step, n_d) || in_passes(0, e0, e_step, n_e)
+    } else {
+        let n = vb.abs();
+        let mut found = false;
+        let mut p = 1;
+        while !found && p * p <= n {
+            if n % p == 0 {
+                found = [p, -p, n / p, -(n / p)]
+                    .iter()
+                    .any(|i| in_passes(*i, vd, d_step, n_d) && in_passes(vb / i, e0, e_step, n_e));
+            }
+            p += 1;
+        }
+        found
+    };
+    if found {
+        registers[f] = 0;
+    }
+    registers[d] = vb;
+    registers[e] = vb;
+    registers[g] = 0;
+
+    Some((n_d * n_e) as u64)
+}
+
+#[derive(Clone, Debug)]
+pub struct Optimized {
+    instructions: Vec<Instruction>,
+    ops: Vec<Op>,
+}
+
+impl Optimized {
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    pub fn program(&self) -> OptimizedProgram<'_> {
+        OptimizedProgram {
+            optimized: self,
+            registers: [0; 26],
+            pc: 0,
+            muls: 0,
+        }
+    }
+
+    pub fn compile(instructions: &[Instruction]) -> Optimized {
+        let mut ops: Vec<Op> = (0..instructions.len())
+            .map(|pc| match_sub_jnz(&instructions[pc..], pc).unwrap_or(Op::Base(instructions[pc])))
+            .collect();
+
+        // Inner loops come first, so the loops around them can build on their ops.
+        for (start, end) in Cfg::build(instructions).closed_loops() {
+            let code = &instructions[start..end];
+            let op = match_mul_loop(code, end)
+                .or_else(|| match_find_factor(code, end))
+                .or_else(|| match_check_composite(code, *ops.get(start + 1)?, end));
+            if let Some(op) = op {
+                ops[start] = op;
+            }
+        }
+
+        Optimized {
+            instructions: instructions.to_vec(),
+            ops,
+        }
+    }
+}
+
+impl fmt::Display for Optimized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (pc, op) in self.ops.iter().enumerate() {
+            writeln!(f, "{:>4}: {}", pc, op)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct OptimizedProgram<'a> {
+    optimized: &'a Optimized,
+    pub registers: [i64; 26],
+    pub pc: usize,
+    pub muls: u64,
+}
+
+impl<'a> OptimizedProgram<'a> {
+    pub fn run(&mut self) -> bool {
+        let registers = &mut self.registers;
+        let instructions = &self.optimized.instructions;
+
+        // Where an op goes next, and the multiplications it did.
+        let jump = match self.optimized.ops[self.pc] {
+            Op::Base(_) => None,
+            Op::SubJnz { g, x, y, target } => {
+                registers[g] = registers[x].wrapping_sub(registers[y]);
+                if registers[g] != 0 {
+                    Some((target, 0))
+                } else {
+                    Some((self.pc + 3, 0))
+                }
+            }
+            Op::MulLoop { counter, step, end } => {
+                mul_loop(registers, &instructions[self.pc..end - 1], counter, step)
+                    .map(|muls| (end, muls))
+            }
+            Op::FindFactor {
+                b,
+                d,
+                e,
+                f,
+                g,
+                step,
+                end,
+            } => find_factor(registers, (b, d, e, f, g), step).map(|muls| (end, muls)),
+            Op::CheckComposite {
+                b,
+                d,
+                e,
+                f,
+                g,
+                e0,
+                d_step,
+                e_step,
+                end,
+            } => check_composite(registers, (b, d, e, f, g), e0, d_step, e_step)
+                .map(|muls| (end, muls)),
+        };
+
+        match jump {
+            Some((pc, muls)) => {
+                self.pc = pc;
+                self.muls += muls;
+            }
+            // Plain instructions, and loops that would run past their end condition.
+            None => instructions[self.pc].execute(registers, &mut self.pc, &mut self.muls),
+        }
+
+        self.pc == self.optimized.ops.len()
+    }
+
+    pub fn run_until_end(&mut self) {
+        while !self.run() {}
+    }
+}
+
+type Thunk = Box<dyn Fn(&mut [i64; 26], &mut u64) -> usize>;
+
+/// The program compiled into one closure per instruction, each of which returns the pc
+/// of the next one with jump targets resolved ahead of time.
+pub struct Compiled {
+    thunks: Vec<Thunk>,
+}
+
+impl Compiled {
+    pub fn program(&self) -> CompiledProgram<'_> {
+        CompiledProgram {
+            compiled: self,
+            registers: [0; 26],
+            pc: 0,
+            muls: 0,
+        }
+    }
+
+    pub fn compile(instructions: &[Instruction]) -> Compiled {
+        let thunks = instructions
+            .iter()
+            .enumerate()
+            .map(|(pc, instruction)| -> Thunk {
+                let next = pc + 1;
+
+                match *instruction {
+                    Instruction::SetN(r, n) => Box::new(move |registers, _| {
+                        registers[r] = n;
+                        next
+                    }),
+                    Instruction::SetR(r1, r2) => Box::new(move |registers, _| {
+                        registers[r1] = registers[r2];
+                        next
+                    }),
+                    Instruction::SubN(r, n) => Box::new(move |registers, _| {
+                        registers[r] = registers[r].wrapping_sub(n);
+                        next
+                    }),
+                    Instruction::SubR(r1, r2) => Box::new(move |registers, _| {
+                        registers[r1] = registers[r1].wrapping_sub(registers[r2]);
+                        next
+                    }),
+                    Instruction::MulN(r, n) => Box::new(move |registers, muls| {
+                        registers[r] = registers[r].wrapping_mul(n);
+                        *muls += 1;
+                        next
+                    }),
+                    Instruction::MulR(r1, r2) => Box::new(move |registers, muls| {
+                        registers[r1] = registers[r1].wrapping_mul(registers[r2]);
+                        *muls += 1;
+                        next
+                    }),
+                    Instruction::Jnz1(offset) => {
+                        let target = (pc as i64 + offset) as usize;
+                        Box::new(move |_, _| target)
+                    }
+                    Instruction::Jnz(r, offset) => {
+                        let target = (pc as i64 + offset) as usize;
+                        Box::new(
+                            move |registers, _| {
+                                if registers[r] != 0 {
+                                    target
+                                } else {
+                                    next
+                                }
+                            },
+                        )
+                    }
+                }
+            })
+            .collect();
+
+        Compiled { thunks }
+    }
+}
+
+pub struct CompiledProgram<'a> {
+    compiled: &'a Compiled,
+    pub registers: [i64; 26],
+    pub pc: usize,
+    pub muls: u64,
+}
+
+impl<'a> CompiledProgram<'a> {
+    pub fn run(&mut self) -> bool {
+        self.pc = (self.compiled.thunks[self.pc])(&mut self.registers, &mut self.muls);
+
+        self.pc == self.compiled.thunks.len()
+    }
+
+    pub fn run_until_end(&mut self) {
+        let thunks = &self.compiled.thunks;
+        let mut pc = self.pc;
+
+        while pc != thunks.len() {
+            pc = (thunks[pc])(&mut self.registers, &mut self.muls);
+        }
+
+        self.pc = pc;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIME_COUNTER: &str = "set b 81
+set c b
+jnz a 2
+jnz 1 5
+mul b 100
+sub b -100000
+set c b
+sub c -17000
+set f 1
+set d 2
+set e 2
+set g d
+mul g e
+sub g b
+jnz g 2
+set f 0
+sub e -1
+set g e
+sub g b
+jnz g -8
+sub d -1
+set g d
+sub g b
+jnz g -13
+jnz f 2
+sub h -1
+set g b
+sub g c
+jnz g 2
+jnz 1 3
+sub b -17
+jnz 1 -23";
+
+    #[test]
+    fn test_parse() {
+        // The kind of the second operand doesn't depend on the first one.
+        assert_eq!(
+            parse("set a 5\nsub b -1\nmul h c\nset g d\njnz 1 -3\njnz f 2"),
+            vec![
+                Instruction::SetN(0, 5),
+                Instruction::SubN(1, -1),
+                Instruction::MulR(7, 2),
+                Instruction::SetR(6, 3),
+                Instruction::Jnz1(-3),
+                Instruction::Jnz(5, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile() {
+        let instructions = parse(PRIME_COUNTER);
+        let optimized = Optimized::compile(&instructions);
+
+        assert_eq!(
+            optimized.ops()[10],
+            Op::CheckComposite {
+                b: 1,
+                d: 3,
+                e: 4,
+                f: 5,
+                g: 6,
+                e0: 2,
+                d_step: 1,
+                e_step: 1,
+                end: 24
+            }
+        );
+        assert_eq!(
+            optimized.ops()[11],
+            Op::FindFactor {
+                b: 1,
+                d: 3,
+                e: 4,
+                f: 5,
+                g: 6,
+                step: 1,
+                end: 20
+            }
+        );
+        assert_eq!(
+            optimized.ops()[26],
+            Op::SubJnz {
+                g: 6,
+                x: 1,
+                y: 2,
+                target: 30
+            }
+        );
+        assert_eq!(optimized.ops()[0], Op::Base(Instruction::SetN(1, 81)));
+
+        let listing = optimized.to_string();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines.len(), instructions.len());
+        assert_eq!(&lines[..2], &["   0: set b 81", "   1: set c b"]);
+        assert_eq!(
+            lines[11],
+            "  11: if d * k == b for k from e by 1 until b { f = 0 }; e = b; g = 0; goto 20"
+        );
+    }
+
+    #[test]
+    fn test_mul_loop() {
+        // a += b * d, with the inner loop adding b one at a time.
+        let instructions = parse(
+            "set b 7
+set d 5
+set c b
+sub a -1
+sub c 1
+jnz c -2
+sub h e
+sub d 1
+jnz d -6",
+        );
+        let optimized = Optimized::compile(&instructions);
+        assert_eq!(
+            optimized.ops()[3],
+            Op::MulLoop {
+                counter: 2,
+                step: -1,
+                end: 6
+            }
+        );
+        assert_eq!(
+            optimized.ops()[2],
+            Op::Base(Instruction::SetR(2, 1)),
+            "the outer loop sets a register"
+        );
+
+        let mut program = Program::from(&instructions);
+        program.registers[4] = -3;
+        program.run_until_end();
+        let mut optimized_program = optimized.program();
+        optimized_program.registers[4] = -3;
+        optimized_program.run_until_end();
+
+        assert_eq!(program.registers, optimized_program.registers);
+        assert_eq!(optimized_program.registers[0], 35);
+        assert_eq!(optimized_program.registers[7], 15);
+    }
+
+    #[test]
+    fn test_idiom_variants() {
+        // The same loops as the prime counter, with other registers, steps of 2, and the
+        // operands of the products and comparisons swapped.
+        let instructions = parse(
+            "set h 1
+set x 3
+set y 3
+set g y
+mul g x
+sub g c
+jnz g 2
+set h 0
+sub y -2
+set g c
+sub g y
+jnz g -8
+sub x -2
+set g c
+sub g x
+jnz g -13",
+        );
+        let optimized = Optimized::compile(&instructions);
+        assert_eq!(
+            optimized.ops()[2],
+            Op::CheckComposite {
+                b: 2,
+                d: 23,
+                e: 24,
+                f: 7,
+                g: 6,
+                e0: 3,
+                d_step: 2,
+                e_step: 2,
+                end: 16
+            }
+        );
+        assert_eq!(
+            optimized.ops()[3],
+            Op::FindFactor {
+                b: 2,
+                d: 23,
+                e: 24,
+                f: 7,
+                g: 6,
+                step: 2,
+                end: 12
+            }
+        );
+
+        for c in (5..60).step_by(2) {
+            let mut program = Program::from(&instructions);
+            program.registers[2] = c;
+            program.run_until_end();
+            let mut optimized_program = optimized.program();
+            optimized_program.registers[2] = c;
+            optimized_program.run_until_end();
+
+            assert_eq!(program.registers, optimized_program.registers, "c = {}", c);
+            assert_eq!(program.muls, optimized_program.muls, "c = {}", c);
+        }
+    }
+
+    #[test]
+    fn test_same_as_interpreter() {
+        let instructions = parse(PRIME_COUNTER);
+        let optimized = Optimized::compile(&instructions);
+
+        let mut program = Program::from(&instructions);
+        program.run_until_end();
+        let mut optimized_program = optimized.program();
+        optimized_program.run_until_end();
+
+        assert_eq!(program.registers, optimized_program.registers);
+        assert_eq!(program.muls, optimized_program.muls);
+        assert_eq!(program.muls, 6241);
+    }
+
+    #[test]
+    fn test_prime_count() {
+        let optimized = Optimized::compile(&parse(PRIME_COUNTER));
+        let mut program = optimized.program();
+        program.registers[0] = 1;
+        program.run_until_end();
+
+        assert_eq!(program.registers[7], 909);
+    }
+
+    #[test]
+    fn test_compiled_same_as_interpreter() {
+        let instructions = parse(PRIME_COUNTER);
+        let compiled = Compiled::compile(&instructions);
+
+        let mut program = Program::from(&instructions);
+        program.run_until_end();
+        let mut compiled_program = compiled.program();
+        compiled_program.run_until_end();
+
+        assert_eq!(program.registers, compiled_program.registers);
+        assert_eq!(program.pc, compiled_program.pc);
+        assert_eq!(program.muls, compiled_program.muls);
+
+        let mut program = Program::from(&instructions);
+        let mut compiled_program = compiled.program();
+        for _ in 0..10000 {
+            assert_eq!(program.run(), compiled_program.run());
+            assert_eq!(program.registers, compiled_program.registers);
+            assert_eq!(program.pc, compiled_program.pc);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use common::aoc::{load_input, print_result, print_time, run_many};
+use common::ksum::k_sum;
 
 fn main() {
     let input = load_input("day01");
@@ -7,8 +8,8 @@ fn main() {
     let (res_part1, dur_part1) = run_many(100000, || part1(&list));
     let (res_part2, dur_part2) = run_many(100000, || part2(&list));
 
-    print_result("P1", res_part1);
-    print_result("P2", res_part2);
+    print_result("P1", res_part1.expect("no two entries sum to 2020"));
+    print_result("P2", res_part2.expect("no three entries sum to 2020"));
 
     print_time("Parse", dur_parse);
     print_time("P1", dur_part1);
@@ -16,48 +17,16 @@ fn main() {
     print_time("Total", dur_parse + dur_part1 + dur_part2);
 }
 
-fn part1(input: &[u32]) -> u32 {
-    let mut set = [false; 2020];
-
-    for n in input.iter().map(|n| *n as usize) {
-        // If the set has the number that would fit with this one.
-        if set[2020 - n] {
-            return ((2020 - n) * n) as u32;
-        }
-
-        // Add it to set.
-        set[n] = true;
-    }
-
-    0
+fn part1(input: &[u32]) -> Option<u32> {
+    product_of_sum(input, 2, 2020)
 }
 
-fn part2(input: &[u32]) -> u32 {
-    let mut min: u32 = 2020;
-
-    for (i, n1) in input.iter().enumerate() {
-        if *n1 < min {
-            min = *n1
-        }
-        if *n1 + min >= 2020 {
-            continue;
-        }
-
-        for (j, n2) in input[i..].iter().enumerate() {
-            let n12 = *n1 + *n2;
-            if n12 > 2020 - min {
-                continue;
-            }
-
-            for n3 in input[i + j..].iter() {
-                if n12 + *n3 == 2020 {
-                    return *n1 * *n2 * *n3;
-                }
-            }
-        }
-    }
+fn part2(input: &[u32]) -> Option<u32> {
+    product_of_sum(input, 3, 2020)
+}
 
-    0
+fn product_of_sum(input: &[u32], k: usize, target: u32) -> Option<u32> {
+    k_sum(input, k, target).map(|indices| indices.iter().map(|i| input[*i]).product())
 }
 
 fn parse_input(input: &str) -> Vec<u32> {
@@ -79,3 +48,17 @@ fn parse_input(input: &str) -> Vec<u32> {
 
     list
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &[u32] = &[1721, 979, 366, 299, 675, 1456];
+
+    #[test]
+    fn test_parts() {
+        assert_eq!(part1(EXAMPLE), Some(514579));
+        assert_eq!(part2(EXAMPLE), Some(241861950));
+        assert_eq!(product_of_sum(EXAMPLE, 2, 4000), None);
+    }
+}
@@ -1,4 +1,5 @@
 use common::aoc::{load_input, print_result, print_time, run_many};
+use common::policy::{CountRange, ExactlyOneAt, Policy};
 
 fn main() {
     let input = load_input("day02");
@@ -17,38 +18,33 @@ fn main() {
 }
 
 fn part1(lines: &[(u32, u32, u8, &str)]) -> u32 {
-    let mut count = 0;
-
-    for (min, max, pwd_char, password) in lines.iter() {
-        let pwd_char = *pwd_char as char;
-        let mut cc: u32 = 0;
-        for c in password.chars() {
-            if c == pwd_char {
-                cc += 1;
-            }
-        }
-
-        if cc >= *min && cc <= *max {
-            count += 1;
-        }
-    }
-
-    count
+    lines
+        .iter()
+        .filter(|(min, max, ch, password)| {
+            let policy = CountRange {
+                ch: *ch,
+                min: *min as usize,
+                max: *max as usize,
+            };
+
+            policy.accepts(password)
+        })
+        .count() as u32
 }
 
 fn part2(lines: &[(u32, u32, u8, &str)]) -> u32 {
-    let mut count = 0;
-
-    for (first, second, pwd_char, password) in lines.iter() {
-        let f = password.as_bytes()[(*first - 1) as usize] == *pwd_char;
-        let s = password.as_bytes()[(*second - 1) as usize] == *pwd_char;
-
-        if f != s {
-            count += 1;
-        }
-    }
-
-    count
+    lines
+        .iter()
+        .filter(|(first, second, ch, password)| {
+            let policy = ExactlyOneAt {
+                ch: *ch,
+                first: *first as usize,
+                second: *second as usize,
+            };
+
+            policy.accepts(password)
+        })
+        .count() as u32
 }
 
 fn parse_input(input: &str) -> Vec<(u32, u32, u8, &str)> {
@@ -88,3 +84,21 @@ fn parse_input(input: &str) -> Vec<(u32, u32, u8, &str)> {
 
     list
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "1-3 a: abcde
+1-3 b: cdefg
+2-9 c: ccccccccc
+";
+
+    #[test]
+    fn test_parts() {
+        let list = parse_input(EXAMPLE);
+
+        assert_eq!(part1(&list), 2);
+        assert_eq!(part2(&list), 1);
+    }
+}
@@ -1,13 +1,22 @@
 use common::aoc::{load_input, print_result, print_time, run_many};
+use std::fmt;
 
-const ZERO: u32 = '0' as u8 as u32;
+const PASSPORT_SCHEMA: &str = "byr required int 1920-2002
+iyr required int 2010-2020
+eyr required int 2020-2030
+hgt required units cm:150-193 in:59-76
+hcl required pattern #[0-9a-f]{6}
+ecl required enum amb blu brn gry grn hzl oth
+pid required pattern [0-9]{9}
+cid optional any";
 
 fn main() {
     let input = load_input("day04");
+    let schema = Schema::parse(PASSPORT_SCHEMA).unwrap();
 
-    let (coll, dur_parse) = run_many(1000, || PassportCollection::parse(&input));
-    let (res_part1, dur_part1) = run_many(100000, || coll.count_noempty_fields());
-    let (res_part2, dur_part2) = run_many(100000, || coll.count_valid());
+    let (records, dur_parse) = run_many(1000, || parse_records(&input));
+    let (res_part1, dur_part1) = run_many(100000, || count_complete(&schema, &records));
+    let (res_part2, dur_part2) = run_many(100000, || count_valid(&schema, &records));
 
     print_result("P1", res_part1);
     print_result("P2", res_part2);
@@ -18,233 +27,307 @@ fn main() {
     print_time("Total", dur_parse + dur_part1 + dur_part2);
 }
 
-struct PassportCollection {
-    passports: Vec<Passport>,
+fn count_complete(schema: &Schema, records: &[Record]) -> usize {
+    records
+        .iter()
+        .filter(|record| {
+            schema
+                .validate(record)
+                .iter()
+                .all(|v| !matches!(v, Violation::Missing(_)))
+        })
+        .count()
 }
 
-impl PassportCollection {
-    pub fn count_noempty_fields(&self) -> usize {
-        let mut count = 0;
+fn count_valid(schema: &Schema, records: &[Record]) -> usize {
+    records
+        .iter()
+        .filter(|record| schema.validate(record).is_empty())
+        .count()
+}
 
-        for pass in self.passports.iter() {
-            if pass.missing_fields() {
-                continue;
-            }
+/// The `key:value` pairs of one record, in the order they were written.
+type Record<'a> = Vec<(&'a str, &'a str)>;
+
+fn parse_records(input: &str) -> Vec<Record<'_>> {
+    input
+        .split("\n\n")
+        .map(|block| {
+            block
+                .split_whitespace()
+                .filter_map(|pair| {
+                    let colon = pair.find(':')?;
+                    Some((&pair[..colon], &pair[colon + 1..]))
+                })
+                .collect::<Record>()
+        })
+        .filter(|record| !record.is_empty())
+        .collect()
+}
 
-            count += 1;
-        }
+#[derive(Debug, Eq, PartialEq)]
+enum Violation {
+    Missing(String),
+    Unknown(String),
+    Duplicate(String),
+    /// The field and its value.
+    Invalid(String, String),
+}
 
-        count
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Missing(field) => write!(f, "{} is missing", field),
+            Violation::Unknown(field) => write!(f, "{} is not a known field", field),
+            Violation::Duplicate(field) => write!(f, "{} is given more than once", field),
+            Violation::Invalid(field, value) => write!(f, "{} has invalid value {}", field, value),
+        }
     }
+}
 
-    pub fn count_valid(&self) -> usize {
-        let mut count = 0;
+struct Field {
+    name: String,
+    required: bool,
+    rule: Rule,
+}
 
-        for pass in self.passports.iter() {
-            if !pass.validate() {
-                continue;
+/// The fields a record may have and what their values must look like.
+struct Schema {
+    fields: Vec<Field>,
+}
+
+impl Schema {
+    /// Every violation in the record, in the order of the schema's fields, followed by
+    /// fields the schema doesn't know.
+    fn validate(&self, record: &[(&str, &str)]) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        for field in self.fields.iter() {
+            let mut values = record.iter().filter(|(k, _)| *k == field.name);
+
+            match values.next() {
+                None if field.required => violations.push(Violation::Missing(field.name.clone())),
+                None => {}
+                Some((_, value)) => {
+                    if !field.rule.accepts(value) {
+                        violations
+                            .push(Violation::Invalid(field.name.clone(), (*value).to_owned()));
+                    }
+                    if values.next().is_some() {
+                        violations.push(Violation::Duplicate(field.name.clone()));
+                    }
+                }
             }
+        }
 
-            count += 1;
+        for (key, _) in record.iter() {
+            if !self.fields.iter().any(|f| f.name == *key) {
+                violations.push(Violation::Unknown((*key).to_owned()));
+            }
         }
 
-        count
+        violations
     }
 
-    pub fn parse(input: &str) -> PassportCollection {
-        let mut passports = Vec::with_capacity(100);
-
-        let mut current_key = String::with_capacity(3);
-        let mut current_value = String::with_capacity(16);
-        let mut current_passport = Passport::new();
-        let mut state = 0;
-        let mut prev = 0 as char;
-        for ch in input.chars() {
-            if ch == '\n' && prev == '\n' {
-                passports.push(current_passport);
-                current_passport = Passport::new();
-                state = 0;
-                current_key.clear();
-                current_value.clear();
-            } else if ch == ' ' || ch == '\n' {
-                state = 0;
-
-                match current_key.as_str() {
-                    "byr" => current_passport.byr.push_str(&current_value),
-                    "iyr" => current_passport.iyr.push_str(&current_value),
-                    "eyr" => current_passport.eyr.push_str(&current_value),
-                    "hgt" => current_passport.hgt.push_str(&current_value),
-                    "hcl" => current_passport.hcl.push_str(&current_value),
-                    "ecl" => current_passport.ecl.push_str(&current_value),
-                    "pid" => current_passport.pid.push_str(&current_value),
-                    _ => {}
+    /// Reads one field per line as `<name> <required|optional> <rule>`, where the rule is
+    /// one of:
+    ///
+    /// - `any`
+    /// - `int <min>-<max>`
+    /// - `units <unit>:<min>-<max> ...`, a number directly followed by one of the units
+    /// - `pattern <pattern>`, with literal characters, `[...]` classes and `{n}`, `{n,m}`,
+    ///   `?`, `*` or `+` after either
+    /// - `enum <value> ...`
+    fn parse(spec: &str) -> Result<Schema, String> {
+        let mut fields = Vec::new();
+
+        for line in spec.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            let mut words = line.split_whitespace();
+            let name = words.next().unwrap().to_owned();
+            let required = match words.next() {
+                Some("required") => true,
+                Some("optional") => false,
+                _ => return Err(format!("expected required or optional: {}", line)),
+            };
+            let args: Vec<&str> = words.collect();
+
+            let rule = match args.split_first() {
+                Some((&"any", [])) => Rule::Any,
+                Some((&"int", [range])) => {
+                    let (min, max) = parse_range(range)?;
+                    Rule::Int(min, max)
                 }
-
-                current_key.clear();
-                current_value.clear();
-            } else if ch == ':' {
-                state = 1;
-            } else {
-                if state == 1 {
-                    current_value.push(ch);
-                } else {
-                    current_key.push(ch);
+                Some((&"units", units)) if !units.is_empty() => Rule::Units(
+                    units
+                        .iter()
+                        .map(|unit| {
+                            let colon = unit.find(':').ok_or_else(|| unit.to_string())?;
+                            let (min, max) = parse_range(&unit[colon + 1..])?;
+                            Ok((unit[..colon].to_owned(), min, max))
+                        })
+                        .collect::<Result<_, String>>()?,
+                ),
+                Some((&"pattern", [pattern])) => Rule::Pattern(Pattern::parse(pattern)?),
+                Some((&"enum", values)) if !values.is_empty() => {
+                    Rule::Enum(values.iter().map(|v| (*v).to_owned()).collect())
                 }
-            }
-
-            prev = ch;
+                _ => return Err(format!("bad rule: {}", line)),
+            };
+
+            fields.push(Field {
+                name,
+                required,
+                rule,
+            });
         }
 
-        passports.push(current_passport);
-
-        PassportCollection { passports }
+        Ok(Schema { fields })
     }
 }
 
-struct Passport {
-    byr: String,
-    iyr: String,
-    eyr: String,
-    hgt: String,
-    hcl: String,
-    ecl: String,
-    pid: String,
+enum Rule {
+    Any,
+    Int(i64, i64),
+    /// Each unit with its own range.
+    Units(Vec<(String, i64, i64)>),
+    Pattern(Pattern),
+    Enum(Vec<String>),
 }
 
-impl Passport {
-    fn missing_fields(&self) -> bool {
-        self.byr.is_empty()
-            || self.iyr.is_empty()
-            || self.eyr.is_empty()
-            || self.hgt.is_empty()
-            || self.hcl.is_empty()
-            || self.ecl.is_empty()
-            || self.pid.is_empty()
+impl Rule {
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            Rule::Any => true,
+            Rule::Int(min, max) => parse_int(value).is_some_and(|n| (*min..=*max).contains(&n)),
+            Rule::Units(units) => {
+                let split = value
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(value.len());
+                let (number, unit) = value.split_at(split);
+
+                units.iter().any(|(name, min, max)| {
+                    name == unit && parse_int(number).is_some_and(|n| (*min..=*max).contains(&n))
+                })
+            }
+            Rule::Pattern(pattern) => pattern.matches(value.as_bytes()),
+            Rule::Enum(values) => values.iter().any(|v| v == value),
+        }
     }
+}
 
-    fn validate(&self) -> bool {
-        if self.missing_fields() {
-            return false;
-        }
+/// A small subset of regular expressions that always has to match the whole value.
+struct Pattern {
+    items: Vec<(CharSet, usize, usize)>,
+}
 
-        if !valid_pid(&self.pid) {
-            return false;
-        }
-        if !valid_color(&self.hcl) {
-            return false;
-        }
-        if !one_of(
-            &self.ecl,
-            &["amb", "blu", "brn", "gry", "grn", "hzl", "oth"],
-        ) {
-            return false;
-        }
+impl Pattern {
+    fn matches(&self, s: &[u8]) -> bool {
+        Pattern::matches_from(&self.items, s)
+    }
 
-        let (height, height_unit) = parse_int_unit(&self.hgt);
-        let valid_height = match height_unit {
-            "cm" => height >= 150 && height <= 193,
-            "in" => height >= 59 && height <= 76,
-            _ => false,
+    fn matches_from(items: &[(CharSet, usize, usize)], s: &[u8]) -> bool {
+        let ((set, min, max), rest) = match items.split_first() {
+            Some(item) => item,
+            None => return s.is_empty(),
         };
-        if !valid_height {
-            return false;
-        }
 
-        let byr = parse_int(&self.byr);
-        if byr < 1920 || byr > 2002 {
-            return false;
-        }
-        let iyr = parse_int(&self.iyr);
-        if iyr < 2010 || iyr > 2020 {
-            return false;
-        }
-        let eyr = parse_int(&self.eyr);
-        if eyr < 2020 || eyr > 2030 {
-            return false;
-        }
+        let available = s.iter().take_while(|b| set.contains(**b)).count();
+        let most = (*max).min(available);
 
-        true
+        (*min..=most)
+            .rev()
+            .any(|n| Pattern::matches_from(rest, &s[n..]))
     }
 
-    fn new() -> Passport {
-        Passport {
-            byr: String::with_capacity(8),
-            iyr: String::with_capacity(8),
-            eyr: String::with_capacity(8),
-            hgt: String::with_capacity(16),
-            hcl: String::with_capacity(16),
-            ecl: String::with_capacity(16),
-            pid: String::with_capacity(16),
-        }
-    }
-}
+    fn parse(s: &str) -> Result<Pattern, String> {
+        let bytes = s.as_bytes();
+        let mut items = Vec::new();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let set = if bytes[i] == b'[' {
+                let end = i + s[i..]
+                    .find(']')
+                    .ok_or_else(|| format!("unclosed [: {}", s))?;
+                let set = CharSet::parse(&bytes[i + 1..end]);
+                i = end + 1;
+                set
+            } else {
+                i += 1;
+                CharSet(vec![(bytes[i - 1], bytes[i - 1])])
+            };
+
+            let (min, max) = match bytes.get(i) {
+                Some(b'?') => (0, 1),
+                Some(b'*') => (0, usize::MAX),
+                Some(b'+') => (1, usize::MAX),
+                Some(b'{') => {
+                    let end = i + s[i..]
+                        .find('}')
+                        .ok_or_else(|| format!("unclosed {{: {}", s))?;
+                    let inner = &s[i + 1..end];
+                    i = end;
+
+                    let parse = |n: &str| n.parse().map_err(|_| format!("bad count: {}", inner));
+                    match inner.find(',') {
+                        Some(comma) => (parse(&inner[..comma])?, parse(&inner[comma + 1..])?),
+                        None => (parse(inner)?, parse(inner)?),
+                    }
+                }
+                _ => {
+                    items.push((set, 1, 1));
+                    continue;
+                }
+            };
 
-fn valid_color(s: &str) -> bool {
-    if s.len() != 7 || !s.starts_with('#') {
-        return false;
-    }
-    for ch in s[1..].chars() {
-        if !((ch >= '0' && ch <= '9') || (ch >= 'a' && ch <= 'f')) {
-            return false;
+            items.push((set, min, max));
+            i += 1;
         }
-    }
 
-    true
+        Ok(Pattern { items })
+    }
 }
 
-fn valid_pid(s: &str) -> bool {
-    if s.len() != 9 {
-        return false;
-    }
+/// Inclusive ranges of bytes.
+struct CharSet(Vec<(u8, u8)>);
 
-    for ch in s.chars() {
-        if ch < '0' || ch > '9' {
-            return false;
-        }
+impl CharSet {
+    fn contains(&self, b: u8) -> bool {
+        self.0.iter().any(|(lo, hi)| b >= *lo && b <= *hi)
     }
 
-    true
-}
+    fn parse(bytes: &[u8]) -> CharSet {
+        let mut ranges = Vec::with_capacity(4);
+        let mut i = 0;
 
-fn one_of(s: &str, values: &[&str]) -> bool {
-    for v in values.iter() {
-        if s == *v {
-            return true;
+        while i < bytes.len() {
+            if i + 2 < bytes.len() && bytes[i + 1] == b'-' {
+                ranges.push((bytes[i], bytes[i + 2]));
+                i += 3;
+            } else {
+                ranges.push((bytes[i], bytes[i]));
+                i += 1;
+            }
         }
-    }
 
-    false
+        CharSet(ranges)
+    }
 }
 
-fn parse_int(s: &str) -> u32 {
-    let mut result = 0;
-
-    for ch in s.chars() {
-        if ch >= '0' && ch <= '9' {
-            result = (result * 10) + ((ch as u32) - ZERO);
-        } else {
-            break;
-        }
+fn parse_int(s: &str) -> Option<i64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
     }
 
-    result
+    s.parse().ok()
 }
 
-fn parse_int_unit(s: &str) -> (u32, &str) {
-    let mut result = 0;
-    let mut pos = 0usize;
-
-    for ch in s.chars() {
-        if ch >= '0' && ch <= '9' {
-            result = (result * 10) + ((ch as u32) - ZERO);
-        } else {
-            return (result, &s[pos..]);
-        }
-
-        pos += 1;
-    }
+fn parse_range(s: &str) -> Result<(i64, i64), String> {
+    let dash = s.find('-').ok_or_else(|| format!("not a range: {}", s))?;
+    let min = parse_int(&s[..dash]).ok_or_else(|| format!("not a range: {}", s))?;
+    let max = parse_int(&s[dash + 1..]).ok_or_else(|| format!("not a range: {}", s))?;
 
-    (result, &s[s.len()..])
+    Ok((min, max))
 }
 
 #[cfg(test)]
@@ -253,7 +336,8 @@ mod tests {
 
     #[test]
     fn test_part2() {
-        let coll = PassportCollection::parse(
+        let schema = Schema::parse(PASSPORT_SCHEMA).unwrap();
+        let records = parse_records(
             "pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980
 hcl:#623a2f
 
@@ -268,9 +352,9 @@ eyr:2022
 iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719
 ",
         );
-        assert_eq!(coll.count_valid(), 4);
+        assert_eq!(count_valid(&schema, &records), 4);
 
-        let coll = PassportCollection::parse(
+        let records = parse_records(
             "eyr:1972 cid:100
 hcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926
 
@@ -286,6 +370,34 @@ eyr:2038 hcl:74454a iyr:2023
 pid:3556412378 byr:2007
 ",
         );
-        assert_eq!(coll.count_valid(), 0);
+        assert_eq!(count_valid(&schema, &records), 0);
+        assert_eq!(count_complete(&schema, &records), 4);
+    }
+
+    #[test]
+    fn test_violations() {
+        let schema = Schema::parse(PASSPORT_SCHEMA).unwrap();
+        let records =
+            parse_records("hgt:59cm ecl:zzz eyr:2038 hcl:#74454a pid:3556412378 foo:1 pid:1");
+
+        assert_eq!(
+            schema.validate(&records[0]),
+            vec![
+                Violation::Missing("byr".to_owned()),
+                Violation::Missing("iyr".to_owned()),
+                Violation::Invalid("eyr".to_owned(), "2038".to_owned()),
+                Violation::Invalid("hgt".to_owned(), "59cm".to_owned()),
+                Violation::Invalid("ecl".to_owned(), "zzz".to_owned()),
+                Violation::Invalid("pid".to_owned(), "3556412378".to_owned()),
+                Violation::Duplicate("pid".to_owned()),
+                Violation::Unknown("foo".to_owned()),
+            ]
+        );
+
+        let pattern = Pattern::parse("a[0-9]{2,3}b?c*").unwrap();
+        assert!(pattern.matches(b"a12") && pattern.matches(b"a123bccc"));
+        assert!(!pattern.matches(b"a1234") && !pattern.matches(b"a1b"));
+        assert!(Schema::parse("x required int 5").is_err());
+        assert!(Schema::parse("x sometimes any").is_err());
     }
 }
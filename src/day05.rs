@@ -1,94 +1,276 @@
 use common::aoc::{load_input, print_result, print_time, run_many};
-
-const R: u8 = 'R' as u8;
-const B: u8 = 'B' as u8;
+use rustc_hash::FxHashMap;
+use std::collections::BTreeSet;
 
 fn main() {
     let input = load_input("day05");
+    let bsp = Bsp::boarding_pass();
 
     let (list, dur_parse) = run_many(1000, || parse_input(&input));
-    let (res_part1, dur_part1) = run_many(100000, || part1(&list));
-    let (res_part2, dur_part2) = run_many(100000, || part2(&list));
+    let (res_part1, dur_part1) = run_many(100000, || part1(&bsp, &list));
+    let (res_part2, dur_part2) = run_many(100000, || part2(&bsp, &list));
+    let (res_part2_surrounded, dur_part2_surrounded) =
+        run_many(1000, || part2_surrounded(&bsp, &list));
 
     print_result("P1", res_part1);
     print_result("P2", res_part2);
+    print_result("P2 SURROUNDED", res_part2_surrounded);
 
     print_time("Parse", dur_parse);
     print_time("P1", dur_part1);
     print_time("P2", dur_part2);
+    print_time("P2 SURROUNDED", dur_part2_surrounded);
     print_time("Total", dur_parse + dur_part1 + dur_part2);
 }
 
-fn part1(list: &[u8]) -> u32 {
-    let mut highest_id = 0;
+fn part1(bsp: &Bsp, list: &[&str]) -> u64 {
+    list.iter()
+        .map(|pass| bsp.decode(pass.as_bytes()).unwrap().id)
+        .max()
+        .unwrap()
+}
 
-    for i in 0..list.len() / 10 {
-        let s = i * 10;
-        let e = s + 10;
-        let id = pass_id(&list[s..e]);
-        if id > highest_id {
-            highest_id = id;
-        }
+fn part2(bsp: &Bsp, list: &[&str]) -> u64 {
+    let map = SeatMap::new(bsp, list.iter().map(|pass| pass.as_bytes()));
+
+    let id = map.empty_between_occupied().next();
+
+    id.expect("position not found")
+}
+
+/// The number of empty seats with every seat around them taken.
+fn part2_surrounded(bsp: &Bsp, list: &[&str]) -> usize {
+    let map = SeatMap::new(bsp, list.iter().map(|pass| pass.as_bytes()));
+
+    map.empty_with_occupied_neighbors(8).len()
+}
+
+fn parse_input(input: &str) -> Vec<&str> {
+    input.lines().filter(|l| !l.is_empty()).collect()
+}
+
+/// The letters that halve one axis, and how many of them there are.
+struct Axis {
+    lower: u8,
+    upper: u8,
+    bits: u32,
+}
+
+impl Axis {
+    fn decode(&self, letters: &[u8]) -> Option<u64> {
+        letters.iter().try_fold(0, |acc, letter| match *letter {
+            l if l == self.lower => Some(acc << 1),
+            l if l == self.upper => Some((acc << 1) | 1),
+            _ => None,
+        })
+    }
+
+    fn size(&self) -> u64 {
+        1 << self.bits
     }
+}
+
+/// Decodes passes made of the row letters followed by the column letters.
+struct Bsp {
+    row: Axis,
+    column: Axis,
+}
 
-    highest_id
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Seat {
+    row: u64,
+    column: u64,
+    id: u64,
 }
 
-fn part2(list: &[u8]) -> u32 {
-    let mut taken = vec![false; 128 * 8];
+impl Bsp {
+    /// `None` if the axes have 64 bits or more between them, as the number of seats has to
+    /// fit in a `u64`.
+    fn new(row: Axis, column: Axis) -> Option<Bsp> {
+        if row.bits + column.bits >= 64 {
+            return None;
+        }
+
+        Some(Bsp { row, column })
+    }
 
-    for i in 0..list.len() / 10 {
-        let s = i * 10;
-        let e = s + 10;
-        taken[pass_id(&list[s..e]) as usize] = true;
+    fn boarding_pass() -> Bsp {
+        Bsp::new(
+            Axis {
+                lower: b'F',
+                upper: b'B',
+                bits: 7,
+            },
+            Axis {
+                lower: b'L',
+                upper: b'R',
+                bits: 3,
+            },
+        )
+        .unwrap()
     }
 
-    for i in 1..taken.len() - 1 {
-        if taken[i - 1] && !taken[i] && taken[i + 1] {
-            return i as u32;
+    /// The seat, or `None` if the pass has the wrong length or letters.
+    fn decode(&self, pass: &[u8]) -> Option<Seat> {
+        let split = self.row.bits as usize;
+        if pass.len() != split + self.column.bits as usize {
+            return None;
         }
+
+        let row = self.row.decode(&pass[..split])?;
+        let column = self.column.decode(&pass[split..])?;
+
+        Some(Seat {
+            row,
+            column,
+            id: row * self.column.size() + column,
+        })
     }
+}
 
-    panic!("position not found")
+/// Which seats are taken, by seat id. Only the taken ones are stored, so the plane can have
+/// far more seats than passes.
+struct SeatMap {
+    rows: u64,
+    columns: u64,
+    occupied: BTreeSet<u64>,
 }
 
-fn parse_input(input: &str) -> Vec<u8> {
-    let mut vec = Vec::with_capacity(input.len());
+impl SeatMap {
+    /// Passes that can't be decoded are skipped.
+    fn new<'a>(bsp: &Bsp, passes: impl Iterator<Item = &'a [u8]>) -> SeatMap {
+        SeatMap {
+            rows: bsp.row.size(),
+            columns: bsp.column.size(),
+            occupied: passes
+                .filter_map(|pass| bsp.decode(pass))
+                .map(|seat| seat.id)
+                .collect(),
+        }
+    }
 
-    for ch in input.chars() {
-        if ch != '\n' {
-            vec.push(ch as u8);
+    fn seat(&self, id: u64) -> Seat {
+        Seat {
+            row: id / self.columns,
+            column: id % self.columns,
+            id,
         }
     }
 
-    vec
-}
+    fn is_occupied(&self, id: u64) -> bool {
+        self.occupied.contains(&id)
+    }
 
-fn pass_id(pass: &[u8]) -> u32 {
-    (if pass[0] == B { 512 } else { 0 })
-        + if pass[1] == B { 256 } else { 0 }
-        + if pass[2] == B { 128 } else { 0 }
-        + if pass[3] == B { 64 } else { 0 }
-        + if pass[4] == B { 32 } else { 0 }
-        + if pass[5] == B { 16 } else { 0 }
-        + if pass[6] == B { 8 } else { 0 }
-        + if pass[7] == R { 4 } else { 0 }
-        + if pass[8] == R { 2 } else { 0 }
-        + if pass[9] == R { 1 } else { 0 }
+    /// Empty seats where the ids right before and after are both taken.
+    fn empty_between_occupied(&self) -> impl Iterator<Item = u64> + '_ {
+        self.occupied
+            .iter()
+            .map(|id| id + 1)
+            .filter(move |id| !self.is_occupied(*id) && self.is_occupied(id + 1))
+    }
+
+    /// Empty seats with at least `min` taken seats among the eight around them in the grid,
+    /// by id. Seats with none taken around them are left out even for a `min` of 0.
+    fn empty_with_occupied_neighbors(&self, min: usize) -> Vec<Seat> {
+        let mut counts: FxHashMap<u64, usize> = FxHashMap::default();
+        for id in self.occupied.iter() {
+            let seat = self.seat(*id);
+            for row in seat.row.saturating_sub(1)..=(seat.row + 1).min(self.rows - 1) {
+                for column in
+                    seat.column.saturating_sub(1)..=(seat.column + 1).min(self.columns - 1)
+                {
+                    let neighbor = row * self.columns + column;
+                    if neighbor != *id {
+                        *counts.entry(neighbor).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        let mut seats: Vec<Seat> = counts
+            .into_iter()
+            .filter(|(id, count)| *count >= min && !self.is_occupied(*id))
+            .map(|(id, _)| self.seat(id))
+            .collect();
+        seats.sort_unstable_by_key(|seat| seat.id);
+
+        seats
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn p(s: &str) -> Vec<u8> {
-        s.chars().map(|c| c as u8).collect()
+    #[test]
+    fn test_pass_id() {
+        let bsp = Bsp::boarding_pass();
+        let id = |pass: &str| bsp.decode(pass.as_bytes()).unwrap().id;
+
+        assert_eq!(id("BFFFBBFRRR"), 567);
+        assert_eq!(id("FFFBBBFRRR"), 119);
+        assert_eq!(id("BBFFBBFRLL"), 820);
+        assert_eq!(
+            bsp.decode(b"BBFFBBFRLL"),
+            Some(Seat {
+                row: 102,
+                column: 4,
+                id: 820
+            })
+        );
+        assert_eq!(bsp.decode(b"BBFFBBFRL"), None);
+        assert_eq!(bsp.decode(b"BBFFBBFRLX"), None);
     }
 
     #[test]
-    fn test_pass_id() {
-        assert_eq!(pass_id(&p("BFFFBBFRRR")), 567);
-        assert_eq!(pass_id(&p("FFFBBBFRRR")), 119);
-        assert_eq!(pass_id(&p("BBFFBBFRLL")), 820);
+    fn test_seat_map() {
+        let bsp = Bsp::new(
+            Axis {
+                lower: b'0',
+                upper: b'1',
+                bits: 2,
+            },
+            Axis {
+                lower: b'a',
+                upper: b'b',
+                bits: 2,
+            },
+        )
+        .unwrap();
+        let passes = ["00aa", "00ab", "00bb", "01aa", "11bb"];
+        let map = SeatMap::new(&bsp, passes.iter().map(|p| p.as_bytes()));
+
+        assert_eq!(map.empty_between_occupied().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(
+            map.empty_with_occupied_neighbors(2)
+                .iter()
+                .map(|s| s.id)
+                .collect::<Vec<_>>(),
+            vec![2, 5, 6]
+        );
+
+        // Too many seats to count with 64 bits.
+        let wide = |bits| Axis {
+            lower: b'0',
+            upper: b'1',
+            bits,
+        };
+        assert!(Bsp::new(wide(32), wide(32)).is_none());
+
+        let bsp = Bsp::new(wide(31), wide(31)).unwrap();
+        let passes = [
+            "1".repeat(62),
+            format!("{}0{}", "1".repeat(30), "1".repeat(31)),
+        ];
+        let map = SeatMap::new(&bsp, passes.iter().map(|p| p.as_bytes()));
+        let last = (1 << 62) - 1;
+        assert_eq!(map.empty_between_occupied().count(), 0);
+        assert_eq!(
+            map.empty_with_occupied_neighbors(2)
+                .iter()
+                .map(|s| s.id)
+                .collect::<Vec<_>>(),
+            vec![last - (1 << 31) - 1, last - 1]
+        );
     }
 }
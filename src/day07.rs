@@ -1,5 +1,5 @@
 use common::aoc::{load_input, print_result, print_time, run_many, run_once};
-use std::collections::BTreeMap;
+use common::graph::{Direction, Graph};
 
 const ZERO: u32 = '0' as u32;
 
@@ -22,121 +22,54 @@ fn main() {
 }
 
 struct RuleSet {
-    map: BTreeMap<String, usize>,
-    bags: Vec<Bag>,
+    graph: Graph<u32>,
 }
 
 impl RuleSet {
     pub fn count_unique_parents(&self, name: &str) -> u32 {
-        let bag = self.bag(name).unwrap();
-        let mut explored = vec![false; self.bags.len()];
-        let mut stack = Vec::with_capacity(64);
-        let mut count = 0;
-
-        explored[bag.index] = true;
-        stack.push(bag);
-
-        while stack.len() > 0 {
-            let bag = stack.pop().unwrap();
-
-            for index in bag.contained_by.iter() {
-                if !explored[*index] {
-                    explored[*index] = true;
-                    count += 1;
-                    stack.push(self.bags.get(*index).unwrap());
-                }
-            }
-        }
+        let bag = self.graph.find(name).unwrap();
 
-        count
+        self.graph.bfs(bag, Direction::Backward).len() as u32 - 1
     }
 
     pub fn count_bags(&self, name: &str) -> u32 {
-        let bag = self.bag(name).unwrap();
-        let mut explored = vec![false; self.bags.len()];
-        let mut stack = Vec::with_capacity(64);
-        let mut count = 0;
-
-        explored[bag.index] = true;
-        stack.push((bag, 1u32));
-
-        while stack.len() > 0 {
-            let (bag, bag_count) = stack.pop().unwrap();
-            count += bag_count;
-
-            for BagLink(index, child_bag_count) in bag.can_contain.iter() {
-                stack.push((self.bags.get(*index).unwrap(), bag_count * child_bag_count));
-            }
-        }
+        let bag = self.graph.find(name).unwrap();
+        let count = self
+            .graph
+            .aggregate(bag, |_, children: &[(u32, u32)]| {
+                1 + children.iter().map(|(c, n)| c * n).sum::<u32>()
+            })
+            .unwrap();
 
         count - 1 // Don't count Goldenboi
     }
 
-    fn bag(&self, s: &str) -> Option<&Bag> {
-        self.map.get(s).map(|f| self.bags.get(*f).unwrap())
-    }
-
-    fn ensure_bag_index(&mut self, s: &str) -> usize {
-        match self.map.get(s) {
-            Some(index) => *index,
-            None => {
-                let index = self.bags.len();
-                let bag = Bag {
-                    index,
-                    can_contain: Vec::with_capacity(4),
-                    contained_by: Vec::with_capacity(8),
-                };
-
-                self.map.insert(String::from(s), index);
-                self.bags.push(bag);
-
-                index
-            }
-        }
-    }
-
     pub fn parse(input: &str) -> RuleSet {
-        let mut rule_set = RuleSet {
-            map: BTreeMap::new(),
-            bags: Vec::with_capacity(256),
-        };
+        let mut graph = Graph::new();
 
         for line in input.lines() {
             let bag_color_index = find_nth(line, ' ', 2);
-            let bag_index = rule_set.ensure_bag_index(&line[..bag_color_index]);
+            let bag_index = graph.node(&line[..bag_color_index]);
             let offset = bag_color_index + 13;
 
-            for part in line[offset..].split(",") {
-                let count = (part.chars().skip(1).next().unwrap() as u32) - ZERO;
+            for part in line[offset..].split(',') {
+                let count = (part.chars().nth(1).unwrap() as u32) - ZERO;
                 if count > 9 {
                     break; // This being non-numeric means this is a "contains no bags" line.
                 }
 
                 let child_bag_color_index = find_nth(part, ' ', 4);
                 let child_bag_color = &part[3..child_bag_color_index];
-                let child_bag_index = rule_set.ensure_bag_index(child_bag_color);
+                let child_bag_index = graph.node(child_bag_color);
 
-                rule_set.bags[bag_index]
-                    .can_contain
-                    .push(BagLink(child_bag_index, count));
-                rule_set.bags[child_bag_index].contained_by.push(bag_index);
+                graph.add_edge(bag_index, child_bag_index, count);
             }
         }
 
-        rule_set
+        RuleSet { graph }
     }
 }
 
-#[derive(Debug)]
-struct Bag {
-    index: usize,
-    can_contain: Vec<BagLink>,
-    contained_by: Vec<usize>,
-}
-
-#[derive(Debug)]
-struct BagLink(usize, u32);
-
 fn find_nth(s: &str, c: char, n: usize) -> usize {
     let mut n = n;
     for (i, c2) in s.chars().enumerate() {
@@ -155,8 +88,7 @@ fn find_nth(s: &str, c: char, n: usize) -> usize {
 mod tests {
     use super::*;
 
-    const RS1_INPUT: &'static str =
-        "light red bags contain 1 bright white bag, 2 muted yellow bags.
+    const RS1_INPUT: &str = "light red bags contain 1 bright white bag, 2 muted yellow bags.
 dark orange bags contain 3 bright white bags, 4 muted yellow bags.
 bright white bags contain 1 shiny gold bag.
 muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
@@ -165,7 +97,7 @@ dark olive bags contain 3 faded blue bags, 4 dotted black bags.
 vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.";
-    const RS2_INPUT: &'static str = "shiny gold bags contain 2 dark red bags.
+    const RS2_INPUT: &str = "shiny gold bags contain 2 dark red bags.
 dark red bags contain 2 dark orange bags.
 dark orange bags contain 2 dark yellow bags.
 dark yellow bags contain 2 dark green bags.
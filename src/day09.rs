@@ -1,4 +1,7 @@
 use common::aoc::{load_input_bytes, print_result, print_time, run_many, run_once};
+use rustc_hash::FxHashMap;
+use std::collections::hash_map::Entry;
+use std::collections::VecDeque;
 
 fn main() {
     let (input, dur_load) = run_once(|| load_input_bytes("day09"));
@@ -6,9 +9,12 @@ fn main() {
     print_time("Load", dur_load);
 
     let (list, dur_parse) = run_many(1000, || parse_input(&input));
-    let ((res_part1, target_pos), dur_part1) = run_many(1000, || part1(&list, 25));
+    let (res_part1, dur_part1) = run_many(1000, || part1(&list, 25));
+    let (res_part1, target_pos) = res_part1.expect("every number is valid");
     let (res_part2, dur_part2) = run_many(100000, || part2(&list, res_part1));
     let (res_part2_alt, dur_part2_alt) = run_many(100000, || part2_alt(&list, target_pos));
+    let res_part2 = res_part2.expect("no range adds up to the invalid number");
+    let res_part2_alt = res_part2_alt.expect("no range adds up to the invalid number");
 
     print_result("P1", res_part1);
     print_result("P2", res_part2);
@@ -21,58 +27,124 @@ fn main() {
     print_time("Total", dur_parse + dur_part1 + dur_part2);
 }
 
-fn part1(data: &[u64], preamble_length: usize) -> (u64, usize) {
-    let mut preamble_pos = 0;
-    let mut preamble = &data[0..preamble_length];
-
-    'outer: for (i, n) in data[preamble_length..].iter().enumerate() {
-        for (i, pre_i) in preamble.iter().enumerate() {
-            for pre_j in preamble[i + 1..].iter() {
-                if *pre_i + *pre_j == *n {
-                    preamble_pos += 1;
-                    preamble = &data[preamble_pos..preamble_pos + preamble_length];
-                    continue 'outer;
+fn part1(data: &[u64], preamble_length: usize) -> Option<(u64, usize)> {
+    invalid_positions(data, preamble_length)
+        .next()
+        .map(|i| (data[i], i))
+}
+
+fn part2(data: &[u64], target: u64) -> Option<u64> {
+    contiguous_ranges(data, target)
+        .next()
+        .map(|(lower, upper)| weakness(&data[lower..=upper]))
+}
+
+/// Checks numbers one at a time against the sums of pairs of different numbers in a sliding
+/// window over the ones before them. The sums are kept up to date as the window moves, so
+/// each number costs one lookup plus one pass over the window.
+struct Validator {
+    window: VecDeque<u64>,
+    window_length: usize,
+    sums: FxHashMap<u64, u32>,
+}
+
+impl Validator {
+    fn new(window_length: usize) -> Validator {
+        Validator {
+            window: VecDeque::with_capacity(window_length + 1),
+            window_length,
+            sums: FxHashMap::default(),
+        }
+    }
+
+    /// Returns whether `n` is valid, or `None` while the window is still filling up.
+    fn push(&mut self, n: u64) -> Option<bool> {
+        let valid = if self.window.len() == self.window_length {
+            let valid = self.sums.contains_key(&n);
+
+            if let Some(oldest) = self.window.pop_front() {
+                for sum in Self::pair_sums(&self.window, oldest) {
+                    if let Entry::Occupied(mut e) = self.sums.entry(sum) {
+                        *e.get_mut() -= 1;
+                        if *e.get() == 0 {
+                            e.remove();
+                        }
+                    }
                 }
             }
+
+            Some(valid)
+        } else {
+            None
+        };
+
+        if self.window_length > 0 {
+            for sum in Self::pair_sums(&self.window, n) {
+                *self.sums.entry(sum).or_insert(0) += 1;
+            }
+            self.window.push_back(n);
         }
 
-        return (*n, i + preamble_length);
+        valid
+    }
+
+    fn pair_sums(window: &VecDeque<u64>, n: u64) -> impl Iterator<Item = u64> + '_ {
+        window
+            .iter()
+            .filter(move |m| **m != n)
+            .filter_map(move |m| m.checked_add(n))
     }
+}
+
+/// The positions of every number that isn't the sum of two different numbers among the
+/// `preamble_length` before it.
+fn invalid_positions(data: &[u64], preamble_length: usize) -> impl Iterator<Item = usize> + '_ {
+    let mut validator = Validator::new(preamble_length);
 
-    return (0, 0);
+    data.iter()
+        .enumerate()
+        .filter(move |(_, n)| validator.push(**n) == Some(false))
+        .map(|(i, _)| i)
 }
 
-fn part2(data: &[u64], target: u64) -> u64 {
+/// Every inclusive range of at least two numbers that adds up to `target`, ordered by where
+/// they end.
+fn contiguous_ranges(data: &[u64], target: u64) -> impl Iterator<Item = (usize, usize)> + '_ {
     let mut lower = 0;
-    let mut upper = 1;
-    let mut sum = data[lower] + data[upper];
-
-    while sum != target {
-        let n = data[upper + 1];
-        if sum + n <= target {
-            sum += n;
-            upper += 1;
-        } else {
+    let mut sum = 0;
+
+    data.iter().enumerate().flat_map(move |(upper, n)| {
+        sum += *n;
+        while sum > target {
             sum -= data[lower];
             lower += 1;
         }
-    }
 
-    let mut smallest = target;
-    let mut largest = 0;
-    for n in data[lower..=upper].iter().cloned() {
-        if n < smallest {
-            smallest = n;
-        }
-        if n > largest {
-            largest = n;
+        // Leading zeros can be left out without changing the sum.
+        let mut last = lower;
+        while last + 1 < upper && data[last] == 0 {
+            last += 1;
         }
-    }
 
-    smallest + largest
+        let lowers = if sum == target && lower < upper {
+            lower..last + 1
+        } else {
+            0..0
+        };
+        lowers.map(move |lower| (lower, upper))
+    })
+}
+
+fn weakness(range: &[u64]) -> u64 {
+    range.iter().min().unwrap() + range.iter().max().unwrap()
 }
 
-fn part2_alt(data: &[u64], target_pos: usize) -> u64 {
+fn part2_alt(data: &[u64], target_pos: usize) -> Option<u64> {
+    // The search starts from a pair of neighbours.
+    if data.len() < 2 {
+        return None;
+    }
+
     let target = data[target_pos];
     let mut lower1 = (target_pos * 3) / 4;
     let mut upper1 = lower1 + 1;
@@ -84,7 +156,13 @@ fn part2_alt(data: &[u64], target_pos: usize) -> u64 {
     let upper;
 
     loop {
-        if upper1 < data.len() - 1 {
+        let forward = upper1 < data.len() - 1;
+        let backward = lower2 < data.len() && lower2 > 0;
+        if !forward && !backward {
+            return None;
+        }
+
+        if forward {
             if lower1 != upper1 {
                 let n = data[upper1 + 1];
                 if sum1 + n <= target {
@@ -94,7 +172,7 @@ fn part2_alt(data: &[u64], target_pos: usize) -> u64 {
                     sum1 -= data[lower1];
                     lower1 += 1;
                 }
-                if sum1 == target {
+                if sum1 == target && lower1 != upper1 {
                     lower = lower1;
                     upper = upper1;
                     break;
@@ -108,7 +186,7 @@ fn part2_alt(data: &[u64], target_pos: usize) -> u64 {
             }
         }
 
-        if lower2 < data.len() && lower2 > 0 {
+        if backward {
             if lower2 != upper2 {
                 let n = data[lower2 - 1];
                 if sum2 + n <= target {
@@ -118,14 +196,14 @@ fn part2_alt(data: &[u64], target_pos: usize) -> u64 {
                     sum2 -= data[upper2];
                     upper2 -= 1;
                 }
-                if sum2 == target {
+                if sum2 == target && lower2 != upper2 {
                     lower = lower2;
                     upper = upper2;
                     break;
                 }
             } else {
                 upper2 -= 1;
-                lower2 -= 2;
+                lower2 = lower2.wrapping_sub(2);
                 if lower2 < data.len() {
                     sum2 = data[lower2] + data[upper2];
                 }
@@ -133,18 +211,7 @@ fn part2_alt(data: &[u64], target_pos: usize) -> u64 {
         }
     }
 
-    let mut smallest = target;
-    let mut largest = 0;
-    for n in data[lower..=upper].iter().cloned() {
-        if n < smallest {
-            smallest = n;
-        }
-        if n > largest {
-            largest = n;
-        }
-    }
-
-    smallest + largest
+    Some(weakness(&data[lower..=upper]))
 }
 
 const NEWLINE: u8 = '\n' as u8;
@@ -179,20 +246,42 @@ mod tests {
     fn test_part1() {
         assert_eq!(
             part1(EXAMPLE, 5),
-            (127, EXAMPLE.iter().position(|p| *p == 127).unwrap())
+            Some((127, EXAMPLE.iter().position(|p| *p == 127).unwrap()))
+        );
+        assert_eq!(invalid_positions(EXAMPLE, 5).collect::<Vec<_>>(), vec![14]);
+        assert_eq!(part1(&EXAMPLE[..14], 5), None);
+
+        // The pair has to be two different numbers, and 0 leaves nothing to check against.
+        let data: Vec<u64> = (1..=20).chain(vec![45, 26, 50, 90]).collect();
+        assert_eq!(
+            invalid_positions(&data, 20).collect::<Vec<_>>(),
+            vec![20, 23]
         );
+        assert_eq!(invalid_positions(&[3, 3, 6, 6, 7], 2).count(), 3);
+        assert_eq!(invalid_positions(&data, 0).count(), data.len());
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(EXAMPLE, 127), 62);
+        assert_eq!(part2(EXAMPLE, 127), Some(62));
+        assert_eq!(part2(EXAMPLE, 1), None);
+        assert_eq!(part2(&[], 1), None);
+
+        assert_eq!(
+            contiguous_ranges(&[0, 0, 3, 2, 0, 1, 3], 3).collect::<Vec<_>>(),
+            vec![(0, 2), (1, 2), (3, 5)]
+        );
+        assert_eq!(contiguous_ranges(&[3], 3).count(), 0);
     }
 
     #[test]
     fn test_part2_alt() {
         assert_eq!(
             part2_alt(EXAMPLE, EXAMPLE.iter().position(|p| *p == 127).unwrap()),
-            62
+            Some(62)
         );
+        assert_eq!(part2_alt(&[1, 2, 100, 5, 7, 9], 2), None);
+        assert_eq!(part2_alt(&[5], 0), None);
+        assert_eq!(part2_alt(&[2, 5], 1), None);
     }
 }
@@ -1,15 +1,19 @@
 use common::aoc::{load_input_bytes, print_result, print_time, run_many, run_once};
+use common::chain::Chain;
+use num::BigUint;
+
+const GAPS: &[u64] = &[1, 2, 3];
 
 fn main() {
     let (input, dur_load) = run_once(|| load_input_bytes("day10"));
 
     print_time("Load", dur_load);
 
-    let (list, dur_parse) = run_many(10000, || parse_input(&input));
-    let (res_part1, dur_part1) = run_many(10000, || part1(&list));
-    let (res_part2, dur_part2) = run_many(10000, || part2(&list));
+    let (chain, dur_parse) = run_many(10000, || Chain::new(&parse_input(&input), GAPS));
+    let (res_part1, dur_part1) = run_many(10000, || part1(&chain));
+    let (res_part2, dur_part2) = run_many(10000, || part2(&chain));
 
-    print_result("P1", res_part1);
+    print_result("P1", res_part1.expect("the adapters can't all be chained"));
     print_result("P2", res_part2);
 
     print_time("Parse", dur_parse);
@@ -18,108 +22,75 @@ fn main() {
     print_time("Total", dur_parse + dur_part1 + dur_part2);
 }
 
-fn part1(list: &[u32]) -> u32 {
-    let mut threes = 0;
-    let mut ones = 0;
-
-    let mut p = 0;
-    for n in list.iter() {
-        let diff = n - p;
-        if diff == 3 {
-            threes += 1;
-        } else if diff == 1 {
-            // The comparison here could be omitted to halve the time, but that felt like cheating.
-            ones += 1;
-        }
+fn part1(chain: &Chain) -> Option<usize> {
+    let histogram = chain.histogram()?;
+    let count = |gap| histogram.get(&gap).cloned().unwrap_or(0);
 
-        p = *n;
-    }
-
-    (threes + 1) * ones
+    Some(count(1) * count(3))
 }
 
-fn part2(list: &[u32]) -> u64 {
-    let mut sums = vec![1u64; list.len()];
-    let mut sum = 0;
-
-    let len = list.len();
-    for i in 1..=len {
-        let i = len - i;
-
-        let item_i = list[i];
-        let mut count = if i == len - 1 { 1 } else { 0 };
-        for j in i + 1..len {
-            let item_j = list[j];
-            if item_j - item_i > 3 {
-                break;
-            }
-
-            count += sums[j];
-        }
-
-        sums[i] = count;
-
-        if item_i <= 3 {
-            sum += sums[i]
-        }
-    }
-
-    sum
+fn part2(chain: &Chain) -> BigUint {
+    chain.count()
 }
 
-const NEWLINE: u8 = '\n' as u8;
-const ZERO: u8 = '0' as u8;
-
-fn parse_input(s: &[u8]) -> Vec<u32> {
-    let mut res: Vec<u32> = vec![0; 128];
+fn parse_input(s: &[u8]) -> Vec<u64> {
+    let mut res = Vec::with_capacity(128);
     let mut current = 0;
 
     for b in s.iter() {
-        if *b == NEWLINE {
-            while current >= res.len() {
-                res.push(0);
-            }
-
-            res[current] = 1;
+        if *b == b'\n' {
+            res.push(current);
             current = 0;
         } else {
             current *= 10;
-            current += (b - ZERO) as usize;
+            current += (b - b'0') as u64;
         }
     }
 
-    let mut pos = 0usize;
-    for i in 1..res.len() {
-        if res[i] == 1 {
-            res[pos] = i as u32;
-            pos += 1;
-        }
-    }
-
-    res.truncate(pos);
-
     res
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num::One;
 
-    const EXAMPLE1: &[u32] = &[1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
-    const EXAMPLE2: &[u32] = &[
+    const EXAMPLE1: &[u64] = &[1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+    const EXAMPLE2: &[u64] = &[
         1, 2, 3, 4, 7, 8, 9, 10, 11, 14, 17, 18, 19, 20, 23, 24, 25, 28, 31, 32, 33, 34, 35, 38,
         39, 42, 45, 46, 47, 48, 49,
     ];
 
     #[test]
     fn test_part1() {
-        assert_eq!(part1(EXAMPLE1), 7 * 5);
-        assert_eq!(part1(EXAMPLE2), 22 * 10);
+        assert_eq!(part1(&Chain::new(EXAMPLE1, GAPS)), Some(7 * 5));
+        assert_eq!(part1(&Chain::new(EXAMPLE2, GAPS)), Some(22 * 10));
+        assert_eq!(part1(&Chain::new(&[1, 5], GAPS)), None);
+
+        let histogram = Chain::new(&[16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4], &[1, 3, 4])
+            .histogram()
+            .unwrap();
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            vec![(1, 7), (3, 4), (4, 1)]
+        );
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(EXAMPLE1), 8);
-        assert_eq!(part2(EXAMPLE2), 19208);
+        assert_eq!(part2(&Chain::new(EXAMPLE1, GAPS)), BigUint::from(8u32));
+        assert_eq!(part2(&Chain::new(EXAMPLE2, GAPS)), BigUint::from(19208u32));
+        assert_eq!(
+            Chain::new(&[1, 5], GAPS)
+                .counts_to_end::<u64>()
+                .map(|c| c[0]),
+            Some(0)
+        );
+
+        // Any subset of 1..100 followed by 100, as the device is out of reach otherwise.
+        let adapters: Vec<u64> = (1..=100).collect();
+        let chain = Chain::new(&adapters, &(1..=101).collect::<Vec<_>>());
+        assert_eq!(chain.counts_to_end::<u64>(), None);
+        assert_eq!(chain.count(), BigUint::one() << 99);
     }
 }
@@ -1,15 +1,5 @@
 use common::aoc::{load_input_bytes, print_result, print_time, run_many, run_once};
-
-const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
-const C_FORWARD: u8 = 'F' as u8;
-const C_LEFT: u8 = 'L' as u8;
-const C_RIGHT: u8 = 'R' as u8;
-const C_EAST: u8 = 'E' as u8;
-const C_SOUTH: u8 = 'S' as u8;
-const C_WEST: u8 = 'W' as u8;
-const C_NORTH: u8 = 'N' as u8;
-const C_ZERO: u8 = '0' as u8;
-const C_NEWLINE: u8 = '\n' as u8;
+use std::ops::{Add, Mul, Sub};
 
 fn main() {
     let (input, dur_load) = run_once(|| load_input_bytes("day12"));
@@ -22,6 +12,8 @@ fn main() {
 
     print_result("P1", res_part1);
     print_result("P2", res_part2);
+    print_path("P1", &heading(&instructions));
+    print_path("P2", &waypoint(&instructions));
 
     print_time("Parse", dur_parse);
     print_time("P1", dur_part1);
@@ -29,55 +21,223 @@ fn main() {
     print_time("Total", dur_parse + dur_part1 + dur_part2);
 }
 
-fn part1(instructions: &[Instruction]) -> i32 {
-    let mut x = 0i32;
-    let mut y = 0i32;
-    let mut dir = 0usize;
+fn part1(instructions: &[Instruction]) -> i64 {
+    heading(instructions).position().manhattan().round() as i64
+}
 
-    for instruction in instructions.iter() {
-        match instruction {
-            Instruction::Forward(l) => {
-                x += DIRECTIONS[dir].0 * l;
-                y += DIRECTIONS[dir].1 * l;
-            }
-            Instruction::Turn(n) => {
-                dir = (dir - *n as usize) % 4;
-            }
-            Instruction::Move(mx, my) => {
-                x += mx;
-                y += my;
+fn part2(instructions: &[Instruction]) -> i64 {
+    waypoint(instructions).position().manhattan().round() as i64
+}
+
+fn heading(instructions: &[Instruction]) -> Navigator<Heading> {
+    let mut navigator = Navigator::new(Heading(Point::new(1.0, 0.0)));
+    navigator.run(instructions);
+
+    navigator
+}
+
+fn waypoint(instructions: &[Instruction]) -> Navigator<Waypoint> {
+    let mut navigator = Navigator::new(Waypoint(Point::new(10.0, -1.0)));
+    navigator.run(instructions);
+
+    navigator
+}
+
+/// Prints where the ship went along the way.
+fn print_path<M: Model>(label: &str, navigator: &Navigator<M>) {
+    let (min, max) = navigator.bounding_box();
+
+    print_result(&format!("{} STOPS", label), navigator.path().len() - 1);
+    print_result(
+        &format!("{} BOX", label),
+        format!("({}, {}) to ({}, {})", min.x, min.y, max.x, max.y),
+    );
+    print_result(&format!("{} FARTHEST", label), navigator.farthest());
+    print_result(
+        &format!("{} TRAVELLED", label),
+        format!("{:.3}", navigator.distance_travelled()),
+    );
+}
+
+/// A position or offset, with north towards negative `y`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+impl Point {
+    const ORIGIN: Point = Point { x: 0.0, y: 0.0 };
+
+    fn new(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+
+    /// Rotates clockwise around the origin. Quarter turns are exact, other angles go through
+    /// floating point trigonometry.
+    fn rotated(self, degrees: i32) -> Point {
+        let Point { x, y } = self;
+
+        match degrees.rem_euclid(360) {
+            0 => self,
+            90 => Point::new(-y, x),
+            180 => Point::new(-x, -y),
+            270 => Point::new(y, -x),
+            degrees => {
+                let (sin, cos) = (degrees as f64).to_radians().sin_cos();
+                Point::new(x * cos - y * sin, x * sin + y * cos)
             }
         }
     }
 
-    x.abs() + y.abs()
+    fn manhattan(self) -> f64 {
+        self.x.abs() + self.y.abs()
+    }
+
+    fn euclidean(self) -> f64 {
+        self.x.hypot(self.y)
+    }
 }
 
-fn part2(instructions: &[Instruction]) -> i32 {
-    let mut x = 0;
-    let mut y = 0;
-    let mut wx = 10;
-    let mut wy = -1;
+impl Add for Point {
+    type Output = Point;
 
-    for instruction in instructions.iter() {
-        match instruction {
-            Instruction::Forward(l) => {
-                x += wx * l;
-                y += wy * l;
-            }
-            Instruction::Turn(n) => {
-                let (rx, ry) = rotate(wx, wy, *n);
-                wx = rx;
-                wy = ry;
-            }
-            Instruction::Move(mx, my) => {
-                wx += mx;
-                wy += my;
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f64> for Point {
+    type Output = Point;
+
+    fn mul(self, n: f64) -> Point {
+        Point::new(self.x * n, self.y * n)
+    }
+}
+
+/// How the instructions steer the ship.
+trait Model {
+    /// Where one step of `F` takes the ship.
+    fn vector(&self) -> Point;
+
+    /// Turns clockwise for a positive angle.
+    fn turn(&mut self, degrees: i32);
+
+    /// Applies a compass move, and returns how far it moves the ship itself.
+    fn shift(&mut self, offset: Point) -> Point;
+}
+
+/// The ship moves along the direction it faces, and compass moves take it sideways.
+struct Heading(Point);
+
+impl Model for Heading {
+    fn vector(&self) -> Point {
+        self.0
+    }
+
+    fn turn(&mut self, degrees: i32) {
+        self.0 = self.0.rotated(degrees);
+    }
+
+    fn shift(&mut self, offset: Point) -> Point {
+        offset
+    }
+}
+
+/// The ship moves towards a waypoint relative to it, and compass moves push the waypoint.
+struct Waypoint(Point);
+
+impl Model for Waypoint {
+    fn vector(&self) -> Point {
+        self.0
+    }
+
+    fn turn(&mut self, degrees: i32) {
+        self.0 = self.0.rotated(degrees);
+    }
+
+    fn shift(&mut self, offset: Point) -> Point {
+        self.0 = self.0 + offset;
+        Point::ORIGIN
+    }
+}
+
+/// Runs instructions against a model, keeping every position the ship has been at.
+struct Navigator<M: Model> {
+    model: M,
+    path: Vec<Point>,
+}
+
+impl<M: Model> Navigator<M> {
+    fn new(model: M) -> Navigator<M> {
+        Navigator {
+            model,
+            path: vec![Point::ORIGIN],
+        }
+    }
+
+    fn position(&self) -> Point {
+        *self.path.last().unwrap()
+    }
+
+    fn step(&mut self, instruction: &Instruction) {
+        let offset = match instruction {
+            Instruction::Move(x, y) => self.model.shift(Point::new(*x as f64, *y as f64)),
+            Instruction::Turn(degrees) => {
+                self.model.turn(*degrees);
+                return;
             }
+            Instruction::Forward(n) => self.model.vector() * *n as f64,
+        };
+
+        if offset != Point::ORIGIN {
+            self.path.push(self.position() + offset);
         }
     }
 
-    x.abs() + y.abs()
+    fn run(&mut self, instructions: &[Instruction]) {
+        for instruction in instructions.iter() {
+            self.step(instruction);
+        }
+    }
+
+    /// The positions the ship stopped at, starting with the origin.
+    fn path(&self) -> &[Point] {
+        &self.path
+    }
+
+    /// The smallest and largest corners of the box around the path.
+    fn bounding_box(&self) -> (Point, Point) {
+        self.path
+            .iter()
+            .fold((self.path[0], self.path[0]), |(min, max), p| {
+                (
+                    Point::new(min.x.min(p.x), min.y.min(p.y)),
+                    Point::new(max.x.max(p.x), max.y.max(p.y)),
+                )
+            })
+    }
+
+    /// The largest manhattan distance from the origin along the path.
+    fn farthest(&self) -> f64 {
+        self.path.iter().map(|p| p.manhattan()).fold(0.0, f64::max)
+    }
+
+    /// The length of the path in straight lines.
+    fn distance_travelled(&self) -> f64 {
+        self.path
+            .windows(2)
+            .map(|w| (w[1] - w[0]).euclidean())
+            .sum()
+    }
 }
 
 fn parse_input(input: &[u8]) -> Vec<Instruction> {
@@ -88,23 +248,23 @@ fn parse_input(input: &[u8]) -> Vec<Instruction> {
 
     for c in input.iter() {
         if parsing_number {
-            if *c == C_NEWLINE {
+            if *c == b'\n' {
                 res.push(match current_inst {
-                    C_FORWARD => Instruction::Forward(current_length),
-                    C_LEFT => Instruction::Turn(-(current_length / 90)),
-                    C_RIGHT => Instruction::Turn(current_length / 90),
-                    C_EAST => Instruction::Move(current_length, 0),
-                    C_SOUTH => Instruction::Move(0, current_length),
-                    C_WEST => Instruction::Move(-current_length, 0),
-                    C_NORTH => Instruction::Move(0, -current_length),
-                    _ => panic!(format!("Unknown instruction: {}", current_inst as char)),
+                    b'F' => Instruction::Forward(current_length),
+                    b'L' => Instruction::Turn(-current_length),
+                    b'R' => Instruction::Turn(current_length),
+                    b'E' => Instruction::Move(current_length, 0),
+                    b'S' => Instruction::Move(0, current_length),
+                    b'W' => Instruction::Move(-current_length, 0),
+                    b'N' => Instruction::Move(0, -current_length),
+                    _ => panic!("Unknown instruction: {}", current_inst as char),
                 });
 
                 current_length = 0;
                 parsing_number = false;
             } else {
                 current_length *= 10;
-                current_length += (*c - C_ZERO) as i32;
+                current_length += (*c - b'0') as i32;
             }
         } else {
             current_inst = *c;
@@ -115,19 +275,55 @@ fn parse_input(input: &[u8]) -> Vec<Instruction> {
     res
 }
 
-fn rotate(x: i32, y: i32, n: i32) -> (i32, i32) {
-    match n {
-        0 => (x, y),
-        -3 | 1 => (-y, x),
-        -2 | 2 => (-x, -y),
-        -1 | 3 => (y, -x),
-        _ => panic!("Incorrect rotation"),
-    }
-}
-
 #[derive(Debug)]
 enum Instruction {
     Move(i32, i32),
+    /// Degrees clockwise.
     Turn(i32),
     Forward(i32),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &[u8] = b"F10\nN3\nF7\nR90\nF11\n";
+
+    #[test]
+    fn test_parts() {
+        let instructions = parse_input(EXAMPLE);
+        assert_eq!(part1(&instructions), 25);
+        assert_eq!(part2(&instructions), 286);
+
+        let turns = parse_input(b"L270\nF2\nR450\nF1\nL720\nF1\n");
+        assert_eq!(part1(&turns), 4);
+    }
+
+    #[test]
+    fn test_path() {
+        let mut navigator = Navigator::new(Waypoint(Point::new(10.0, -1.0)));
+        navigator.run(&parse_input(EXAMPLE));
+        assert_eq!(
+            navigator.path(),
+            &[
+                Point::ORIGIN,
+                Point::new(100.0, -10.0),
+                Point::new(170.0, -38.0),
+                Point::new(214.0, 72.0),
+            ]
+        );
+        assert_eq!(
+            navigator.bounding_box(),
+            (Point::new(0.0, -38.0), Point::new(214.0, 72.0))
+        );
+        assert_eq!(navigator.farthest(), 286.0);
+
+        // Three 120 degree turns make a triangle that ends up back at the start.
+        let mut navigator = Navigator::new(Heading(Point::new(1.0, 0.0)));
+        navigator.run(&parse_input(b"F10\nR120\nF10\nR120\nF10\n"));
+        assert!(navigator.position().euclidean() < 1e-9);
+        assert!((navigator.distance_travelled() - 30.0).abs() < 1e-9);
+        let (min, max) = navigator.bounding_box();
+        assert!((max.y - min.y - 75f64.sqrt()).abs() < 1e-9);
+    }
+}
@@ -1,8 +1,7 @@
 use common::aoc::{load_input, print_result, print_time, run_many, run_once};
+use common::bitset::{BitSet, HeapBitSet};
+use num::{BigUint, One};
 use rustc_hash::FxHashMap;
-use std::collections::BTreeMap;
-
-const ZERO_U64: u64 = '0' as u64;
 
 fn main() {
     let (input, dur_load) = run_once(|| load_input("day14"));
@@ -12,99 +11,241 @@ fn main() {
     let (instructions, dur_parse) = run_many(1000, || parse_input(&input));
     let (res_part1, dur_part1) = run_many(1000, || part1(&instructions));
     let (res_part2, dur_part2) = run_many(1000, || part2(&instructions));
+    let (res_part2_concrete, dur_part2_concrete) = run_many(100, || part2_concrete(&instructions));
 
     print_result("P1", res_part1);
     print_result("P2", res_part2);
+    print_result("P2 CONCRETE", res_part2_concrete);
 
     print_time("Parse", dur_parse);
     print_time("P1", dur_part1);
     print_time("P2", dur_part2);
+    print_time("P2 CONCRETE", dur_part2_concrete);
     print_time("Total", dur_parse + dur_part1 + dur_part2);
 }
 
-fn part1(instructions: &[Instruction]) -> u64 {
-    let mut memory = [0u64; 65536];
-    let mut or_mask = 0u64;
-    let mut and_mask = 0u64;
+fn part1(instructions: &[Instruction]) -> BigUint {
+    run(
+        instructions,
+        ConcreteMemory::default(),
+        |mask, address, value| {
+            (
+                AddressPattern::new(address.clone(), HeapBitSet::new()),
+                mask.apply_value(value),
+            )
+        },
+    )
+}
+
+fn part2(instructions: &[Instruction]) -> BigUint {
+    run(
+        instructions,
+        PatternMemory::default(),
+        |mask, address, value| (mask.apply_address(address), value.clone()),
+    )
+}
+
+fn part2_concrete(instructions: &[Instruction]) -> BigUint {
+    run(
+        instructions,
+        ConcreteMemory::default(),
+        |mask, address, value| (mask.apply_address(address), value.clone()),
+    )
+}
+
+/// Runs the program, with `decode` turning each write into the addresses and the value to
+/// store, and returns the sum of everything left in memory.
+fn run<M: Memory>(
+    instructions: &[Instruction],
+    mut memory: M,
+    decode: impl Fn(&Mask, &HeapBitSet, &HeapBitSet) -> (AddressPattern, HeapBitSet),
+) -> BigUint {
+    let mut mask = Mask::default();
 
     for instruction in instructions.iter() {
         match instruction {
-            Instruction::Mask {
-                new_or_mask,
-                new_and_mask,
-                new_flippers: _,
-            } => {
-                or_mask = *new_or_mask;
-                and_mask = *new_and_mask;
-            }
-            Instruction::Memory(idx, value) => {
-                memory[*idx as usize] = (*value | or_mask) & and_mask;
+            Instruction::Mask(new_mask) => mask = new_mask.clone(),
+            Instruction::Memory(address, value) => {
+                let (addresses, value) = decode(&mask, address, value);
+                memory.write(addresses, value);
             }
         }
     }
 
-    memory.iter().cloned().sum()
+    memory.sum()
 }
 
-fn part2(instructions: &[Instruction]) -> u64 {
-    let mut memory = FxHashMap::default();
-    let mut or_mask = 0u64;
-    let mut flippers = Vec::with_capacity(36);
-    let mut flipper_combos = 0u64;
+/// The addresses where the `floating` bits take every value, and the others are those of
+/// `fixed`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct AddressPattern {
+    fixed: HeapBitSet,
+    floating: HeapBitSet,
+}
 
-    for instruction in instructions.iter() {
-        match instruction {
-            Instruction::Mask {
-                new_or_mask,
-                new_and_mask: _,
-                new_flippers,
-            } => {
-                or_mask = *new_or_mask;
-                flippers.clear();
-                flipper_combos = 1;
-                let mut current_bit = 34359738368u64; // 2^35
-                while current_bit > 0 {
-                    if new_flippers & current_bit == current_bit {
-                        flippers.push(current_bit);
-                        flipper_combos *= 2;
-                    }
-
-                    current_bit >>= 1;
-                }
+impl AddressPattern {
+    fn new(fixed: HeapBitSet, floating: HeapBitSet) -> AddressPattern {
+        AddressPattern {
+            fixed: &fixed - &floating,
+            floating,
+        }
+    }
+
+    fn len(&self) -> BigUint {
+        BigUint::one() << self.floating.len()
+    }
+
+    fn intersects(&self, other: &AddressPattern) -> bool {
+        // Only bits that both fix can tell them apart.
+        let differ = &self.fixed ^ &other.fixed;
+        (&(&differ - &self.floating) - &other.floating).is_empty()
+    }
+
+    /// Splits the addresses that aren't in `other` into disjoint patterns. Each floating bit
+    /// that `other` fixes gives one piece, where that bit differs from `other` and the ones
+    /// handled before it match.
+    fn subtract(&self, other: &AddressPattern) -> Vec<AddressPattern> {
+        if !self.intersects(other) {
+            return vec![self.clone()];
+        }
+
+        let mut pieces = Vec::new();
+        let mut rest = self.clone();
+        for bit in (&self.floating - &other.floating).iter() {
+            let mut floating = rest.floating.clone();
+            floating.remove(bit);
+
+            let mut differs = rest.fixed.clone();
+            let mut matches = rest.fixed.clone();
+            if other.fixed.contains(bit) {
+                matches.insert(bit);
+            } else {
+                differs.insert(bit);
             }
-            Instruction::Memory(idx, value) => {
-                let base_idx = idx | or_mask;
-
-                memory.insert(base_idx, *value);
-
-                for current_flip in 1..=flipper_combos {
-                    let mut idx = base_idx;
-                    for flip_pos in 0..(flippers.len() as u64) {
-                        let flip_pos_mask = 1 << flip_pos;
-                        if flip_pos_mask & current_flip == flip_pos_mask {
-                            idx |= flippers[flip_pos as usize];
-                        } else {
-                            idx &= 68719476735u64 ^ flippers[flip_pos as usize];
-                        }
-                    }
-
-                    memory.insert(idx, *value);
-                }
+
+            pieces.push(AddressPattern::new(differs, floating.clone()));
+            rest = AddressPattern::new(matches, floating);
+        }
+
+        pieces
+    }
+
+    fn addresses(&self) -> impl Iterator<Item = HeapBitSet> + '_ {
+        self.floating
+            .subsets()
+            .map(move |flipped| &self.fixed | &flipped)
+    }
+}
+
+trait Memory {
+    fn write(&mut self, addresses: AddressPattern, value: HeapBitSet);
+
+    fn sum(&self) -> BigUint;
+}
+
+/// Stores a value for every address written to, which is slow when writes float over many
+/// bits.
+#[derive(Default)]
+struct ConcreteMemory(FxHashMap<HeapBitSet, HeapBitSet>);
+
+impl Memory for ConcreteMemory {
+    fn write(&mut self, addresses: AddressPattern, value: HeapBitSet) {
+        for address in addresses.addresses() {
+            self.0.insert(address, value.clone());
+        }
+    }
+
+    fn sum(&self) -> BigUint {
+        self.0.values().map(number).sum()
+    }
+}
+
+/// Stores each write as a whole pattern, cutting the addresses it overwrites out of the
+/// earlier ones so that they stay disjoint.
+#[derive(Default)]
+struct PatternMemory(Vec<(AddressPattern, HeapBitSet)>);
+
+impl Memory for PatternMemory {
+    fn write(&mut self, addresses: AddressPattern, value: HeapBitSet) {
+        let mut writes = Vec::with_capacity(self.0.len() + 1);
+        for (pattern, old_value) in self.0.drain(..) {
+            for piece in pattern.subtract(&addresses) {
+                writes.push((piece, old_value.clone()));
             }
         }
+
+        writes.push((addresses, value));
+        self.0 = writes;
+    }
+
+    fn sum(&self) -> BigUint {
+        self.0.iter().map(|(p, v)| p.len() * number(v)).sum()
     }
+}
+
+/// The bits of a number, lowest first, which lets masks and addresses be any width.
+fn bits(n: &BigUint) -> HeapBitSet {
+    let words = n
+        .to_bytes_le()
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .rev()
+                .fold(0, |word, byte| (word << 8) | *byte as u64)
+        })
+        .collect();
+
+    HeapBitSet::from_words(words)
+}
+
+fn number(bits: &HeapBitSet) -> BigUint {
+    let bytes: Vec<u8> = bits
+        .words()
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect();
 
-    memory.values().cloned().sum()
+    BigUint::from_bytes_le(&bytes)
+}
+
+/// Bits that a mask sets, clears, or lets float. Anything past its width is left alone.
+#[derive(Clone, Debug, Default)]
+struct Mask {
+    ones: HeapBitSet,
+    zeros: HeapBitSet,
+    floating: HeapBitSet,
+}
+
+impl Mask {
+    fn apply_value(&self, value: &HeapBitSet) -> HeapBitSet {
+        &(value | &self.ones) - &self.zeros
+    }
+
+    fn apply_address(&self, address: &HeapBitSet) -> AddressPattern {
+        AddressPattern::new(address | &self.ones, self.floating.clone())
+    }
+
+    /// Parses a mask as wide as the string, with the most significant bit first.
+    fn parse(s: &str) -> Mask {
+        let mut mask = Mask::default();
+        for (bit, c) in s.chars().rev().enumerate() {
+            match c {
+                '1' => mask.ones.insert(bit),
+                '0' => mask.zeros.insert(bit),
+                'X' => mask.floating.insert(bit),
+                _ => panic!("bad bit"),
+            }
+        }
+
+        mask
+    }
 }
 
 #[derive(Debug)]
 enum Instruction {
-    Mask {
-        new_or_mask: u64,
-        new_and_mask: u64,
-        new_flippers: u64,
-    },
-    Memory(u64, u64),
+    Mask(Mask),
+    Memory(HeapBitSet, HeapBitSet),
 }
 
 fn parse_input(input: &str) -> Vec<Instruction> {
@@ -120,36 +261,11 @@ fn parse_input(input: &str) -> Vec<Instruction> {
         let right = &line[split_idx + 3..];
 
         if left == "mask" {
-            let mut current_bit = 34359738368u64; // 2^35
-            let mut new_or_mask = 0u64;
-            let mut new_and_mask = 68719476735u64; // 2^36 - 1
-            let mut new_flippers = 0u64;
-            for c in right.chars() {
-                match c {
-                    '1' => {
-                        new_or_mask |= current_bit;
-                    }
-                    '0' => {
-                        new_and_mask ^= current_bit;
-                    }
-                    'X' => {
-                        new_flippers |= current_bit;
-                    }
-                    _ => panic!("bad bit"),
-                }
-
-                current_bit >>= 1;
-            }
-
-            instructions.push(Instruction::Mask {
-                new_or_mask,
-                new_and_mask,
-                new_flippers,
-            });
+            instructions.push(Instruction::Mask(Mask::parse(right)));
         } else {
             instructions.push(Instruction::Memory(
-                parse_u64(&left[4..left.len() - 1]),
-                parse_u64(right),
+                bits(&left[4..left.len() - 1].parse().unwrap()),
+                bits(&right.parse().unwrap()),
             ));
         }
     }
@@ -157,30 +273,109 @@ fn parse_input(input: &str) -> Vec<Instruction> {
     instructions
 }
 
-fn parse_u64(s: &str) -> u64 {
-    let mut res = 0;
-
-    for c in s.chars() {
-        res *= 10;
-        res += (c as u64) - ZERO_U64;
-    }
-
-    res
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLE_1: &str = "mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X
+mem[8] = 11
+mem[7] = 101
+mem[8] = 0";
+
     const EXAMPLE_2: &str = "mask = 000000000000000000000000000000X1001X
 mem[42] = 100
 mask = 00000000000000000000000000000000X0XX
 mem[26] = 1";
 
+    fn pattern(fixed: u64, floating: u64) -> AddressPattern {
+        AddressPattern::new(
+            HeapBitSet::from_words(vec![fixed]),
+            HeapBitSet::from_words(vec![floating]),
+        )
+    }
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(part1(&parse_input(EXAMPLE_1)), BigUint::from(165u32));
+        assert_eq!(
+            part1(&parse_input("mask = 1X0\nmem[1] = 7")),
+            BigUint::from(6u32)
+        );
+    }
+
     #[test]
     fn test_part2() {
         let instructions = parse_input(EXAMPLE_2);
 
-        assert_eq!(part2(&instructions), 208);
+        assert_eq!(part2(&instructions), BigUint::from(208u32));
+        assert_eq!(part2_concrete(&instructions), BigUint::from(208u32));
+
+        let overlapping = parse_input(
+            "mask = X1X0X
+mem[3] = 5
+mask = 0X1XX
+mem[8] = 7
+mask = 00000
+mem[30] = 1
+mask = XXXXX
+mem[0] = 2
+mask = 1X01X
+mem[5] = 9",
+        );
+        assert_eq!(part2(&overlapping), part2_concrete(&overlapping));
+
+        // Far too many addresses to write one at a time.
+        let wide = parse_input(&format!(
+            "mask = {}\nmem[0] = 3\nmask = 1{}\nmem[0] = 5",
+            "X".repeat(64),
+            "X".repeat(63)
+        ));
+        assert_eq!(
+            part2(&wide),
+            (BigUint::one() << 63) * 3u32 + (BigUint::one() << 63) * 5u32
+        );
+    }
+
+    #[test]
+    fn test_wider_than_64_bits() {
+        let instructions = parse_input(&format!(
+            "mask = 1{}\nmem[5] = 3\nmask = X{}1\nmem[18446744073709551616] = 4",
+            "0".repeat(99),
+            "0".repeat(69)
+        ));
+
+        // Values and addresses get bit 99 and bit 0 set by the masks. The second write
+        // floats bit 70 of the address above 2^64.
+        assert_eq!(part1(&instructions), (BigUint::one() << 99) + 1u32);
+        assert_eq!(part2(&instructions), BigUint::from(3u32 + 4 + 4));
+        assert_eq!(part2_concrete(&instructions), part2(&instructions));
+
+        let floating = parse_input(&format!(
+            "mask = {}\nmem[0] = 3\nmask = 1{}\nmem[0] = 5",
+            "X".repeat(100),
+            "X".repeat(99)
+        ));
+        assert_eq!(
+            part2(&floating),
+            (BigUint::one() << 99) * 3u32 + (BigUint::one() << 99) * 5u32
+        );
+    }
+
+    #[test]
+    fn test_subtract() {
+        let a = pattern(0b0000, 0b1111);
+        let b = pattern(0b1010, 0b0001);
+        let pieces = a.subtract(&b);
+
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(
+            pieces.iter().map(|p| p.len()).sum::<BigUint>(),
+            BigUint::from(16u32 - 2)
+        );
+        assert!(pieces.iter().all(|p| !p.intersects(&b)));
+        assert_eq!(
+            pattern(0b0100, 0b0011).subtract(&b),
+            vec![pattern(0b0100, 0b0011)]
+        );
     }
 }
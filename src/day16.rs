@@ -1,4 +1,6 @@
 use common::aoc::{load_input, print_result, print_time, run_many, run_once};
+use common::bitset::{BitSet, HeapBitSet};
+use common::solve::solve;
 
 const U64_ZERO: u64 = '0' as u64;
 
@@ -30,7 +32,7 @@ fn part1(input: &Input) -> (u64, Vec<usize>) {
         for n in input.ticket_data[*ticket_start..*ticket_end].iter() {
             let i = *n as usize;
 
-            if i >= max_value || input.map[i] == 0 {
+            if i >= max_value || input.map[i].is_empty() {
                 result += n;
                 valid = false;
             }
@@ -60,52 +62,23 @@ fn part2(input: &Input, valid_tickets: &[usize]) -> u64 {
 
 fn determine_fields(input: &Input, valid_tickets: &[usize]) -> Vec<usize> {
     let (_, ticket_length) = input.tickets[0];
-    let mut field_masks = vec![usize::max_value(); ticket_length];
+    let mut field_masks = vec![HeapBitSet::full(input.rules.len()); ticket_length];
 
     for (ticket_start, ticket_end) in valid_tickets.iter().map(|v| &input.tickets[*v]) {
         for (i, n) in input.ticket_data[*ticket_start..*ticket_end]
             .iter()
             .enumerate()
         {
-            field_masks[i] &= input.map[*n as usize]
+            field_masks[i].intersect_with(&input.map[*n as usize]);
         }
     }
 
-    let mut field_assigned = vec![false; ticket_length];
-    let mut rule_assigned = vec![false; input.rules.len()];
-    let mut assigned_fields = vec![0usize; ticket_length];
-    let mut assignment_count = 0usize;
-    while assignment_count < ticket_length {
-        for (i, field_mask) in field_masks.iter().enumerate() {
-            if field_assigned[i] {
-                continue;
-            }
-
-            if field_mask.count_ones() as usize == (assignment_count + 1) {
-                for n in 0..input.rules.len() {
-                    if rule_assigned[n] {
-                        continue;
-                    }
-
-                    let n_mask = 1 << n;
-                    if field_mask & n_mask == n_mask {
-                        field_assigned[i] = true;
-                        rule_assigned[n] = true;
-                        assigned_fields[i] = n;
-                        assignment_count += 1;
-                        break;
-                    }
-                }
-            }
-        }
-    }
-
-    return assigned_fields;
+    solve(&field_masks, input.rules.len()).unwrap()
 }
 
 #[derive(Debug)]
 struct Input<'a> {
-    map: Vec<usize>,
+    map: Vec<HeapBitSet>,
     rules: Vec<Rule<'a>>,
     ticket_data: Vec<u64>,
     tickets: Vec<(usize, usize)>,
@@ -174,15 +147,13 @@ impl<'a> Input<'a> {
             }
         }
 
-        let mut map = vec![0usize; highest as usize + 1];
+        let mut map = vec![HeapBitSet::with_capacity(rules.len()); highest as usize + 1];
         for (i, rule) in rules.iter().enumerate() {
-            let bit = 1usize << i;
-
-            for n in rule.min1 as usize..=rule.max1 as usize {
-                map[n] |= bit;
+            for matches in map[rule.min1 as usize..=rule.max1 as usize].iter_mut() {
+                matches.insert(i);
             }
-            for n in rule.min2 as usize..=rule.max2 as usize {
-                map[n] |= bit;
+            for matches in map[rule.min2 as usize..=rule.max2 as usize].iter_mut() {
+                matches.insert(i);
             }
         }
 
@@ -223,6 +194,7 @@ struct Rule<'a> {
 mod tests {
     use super::*;
 
+    #[test]
     fn test_part2() {
         let input = Input::parse(
             "class: 0-1 or 4-19
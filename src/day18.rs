@@ -1,8 +1,8 @@
-use common::aoc::{load_input_bytes, print_result, print_time, run_many, run_once};
-use smallvec::SmallVec;
+use common::aoc::{load_input, print_result, print_time, run_many, run_once};
+use common::expr::{tokenize, Assoc, Expr, Grammar, Op, Token};
 
 fn main() {
-    let (input, dur_load) = run_once(|| load_input_bytes("day18"));
+    let (input, dur_load) = run_once(|| load_input("day18"));
 
     print_time("Load", dur_load);
 
@@ -20,113 +20,35 @@ fn main() {
 }
 
 fn part1(input: &Input) -> i64 {
-    let mut sum = 0;
+    let grammar = p1_grammar();
 
-    for line in input.iter() {
-        sum += solve_one(line);
-    }
-
-    sum
+    input.iter().map(|line| solve_one(line, &grammar)).sum()
 }
 
 fn part2(input: &Input) -> i64 {
-    let mut sum = 0;
+    let grammar = p2_grammar();
 
-    for line in input.iter() {
-        let (res, _) = solve_one_p2(line);
-        sum += res;
-    }
-
-    sum
+    input.iter().map(|line| solve_one(line, &grammar)).sum()
 }
 
-fn solve_one(tokens: &[Token]) -> i64 {
-    let mut acc = 0;
-    let mut is_adding = true;
-    let mut stack: SmallVec<[(i64, bool); 4]> = SmallVec::new();
-    let mut skip = 0;
-
-    for token in tokens.iter() {
-        if skip > 0 {
-            skip -= 1;
-            continue;
-        }
-
-        match token {
-            Token::Operand(n) => {
-                if is_adding {
-                    acc += *n;
-                } else {
-                    acc *= *n;
-                }
-            }
-            Token::Add => {
-                is_adding = true;
-            }
-            Token::Multiply => {
-                is_adding = false;
-            }
-            Token::ParaStart => {
-                stack.push((acc, is_adding));
-                acc = 0;
-                is_adding = true;
-            }
-            Token::ParaEnd => {
-                let (n, was_adding) = stack.pop().unwrap();
-
-                if was_adding {
-                    acc += n;
-                } else {
-                    acc *= n;
-                }
-            }
-        }
+/// Left to right, no matter the operator.
+fn p1_grammar() -> Grammar {
+    Grammar {
+        binary: vec![(Op::Add, 1, Assoc::Left), (Op::Mul, 1, Assoc::Left)],
+        unary_minus: None,
     }
-
-    acc
 }
 
-fn solve_one_p2(tokens: &[Token]) -> (i64, usize) {
-    let mut acc = 0;
-    let mut pos = 0;
-    let mut skip = 0;
-    let mut mul_stack: SmallVec<[i64; 16]> = SmallVec::new();
-
-    for token in tokens.iter() {
-        pos += 1;
-        if skip > 0 {
-            skip -= 1;
-            continue;
-        }
-
-        match token {
-            Token::Operand(n) => {
-                acc += *n;
-            }
-            Token::Add => {}
-            Token::Multiply => {
-                mul_stack.push(acc);
-                acc = 0;
-            }
-            Token::ParaStart => {
-                let (n, new_skip) = solve_one_p2(&tokens[pos..]);
-
-                skip = new_skip;
-                acc += n;
-            }
-            Token::ParaEnd => {
-                break;
-            }
-        }
+/// Addition before multiplication.
+fn p2_grammar() -> Grammar {
+    Grammar {
+        binary: vec![(Op::Add, 2, Assoc::Left), (Op::Mul, 1, Assoc::Left)],
+        unary_minus: None,
     }
+}
 
-    let prod: i64 = mul_stack.iter().cloned().product();
-
-    if acc > 0 {
-        (acc * prod, pos)
-    } else {
-        (prod, pos)
-    }
+fn solve_one(tokens: &[Token], grammar: &Grammar) -> i64 {
+    Expr::from_tokens(tokens, grammar).unwrap().eval().unwrap()
 }
 
 #[derive(Debug)]
@@ -140,89 +62,52 @@ impl Input {
         self.lines.iter().map(move |(s, e)| &self.tokens[*s..*e])
     }
 
-    fn parse(input: &[u8]) -> Input {
+    fn parse(input: &str) -> Input {
         let mut tokens = Vec::with_capacity(256);
         let mut lines = Vec::with_capacity(64);
-        let mut current = 0;
-        let mut start = 0;
-        let mut has_number = false;
-
-        for b in input.iter() {
-            if has_number && (*b < b'0' || *b > b'9') {
-                tokens.push(Token::Operand(current));
-                current = 0;
-                has_number = false;
-            }
 
-            match *b {
-                b'\n' => {
-                    if start == tokens.len() {
-                        continue;
-                    }
-
-                    lines.push((start, tokens.len()));
-                    start = tokens.len();
-                }
-
-                b'+' => {
-                    tokens.push(Token::Add);
-                }
-                b'*' => {
-                    tokens.push(Token::Multiply);
-                }
-
-                b'(' => {
-                    tokens.push(Token::ParaStart);
-                }
-                b')' => {
-                    tokens.push(Token::ParaEnd);
-                }
-
-                b'0'..=b'9' => {
-                    current += (current * 10) + (*b - b'0') as i64;
-                    has_number = true;
-                }
-
-                b' ' => {}
-
-                _ => {
-                    panic!(format!("Unknown token: {}", *b as char));
-                }
+        for line in input.lines() {
+            if line.is_empty() {
+                continue;
             }
+
+            let start = tokens.len();
+            tokens.extend(tokenize(line).unwrap());
+            lines.push((start, tokens.len()));
         }
 
         Input { tokens, lines }
     }
 }
 
-#[derive(Debug)]
-enum Token {
-    Operand(i64),
-    Add,
-    Multiply,
-    ParaStart,
-    ParaEnd,
-}
-
 #[cfg(test)]
 pub mod tests {
     use super::*;
 
-    const P2_EXAMPLE: &[u8] = b"1 + (2 * 3) + (4 * (5 + 6))
+    const P2_EXAMPLE: &str = "1 + (2 * 3) + (4 * (5 + 6))
 2 * 3 + (4 * 5)
 5 + (8 * 3 + 9 + 3 * 4 * 3)
 5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))
 ((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2
 ";
+    const P1_ANSWERS: &[i64] = &[51, 26, 437, 12240, 13632];
     const P2_ANSWERS: &[i64] = &[51, 46, 1445, 669060, 23340];
 
+    #[test]
+    fn test_part1() {
+        let input = Input::parse(P2_EXAMPLE);
+
+        for (i, line) in input.iter().enumerate() {
+            assert_eq!(solve_one(line, &p1_grammar()), P1_ANSWERS[i]);
+        }
+    }
+
     #[test]
     fn test_part2() {
         let input = Input::parse(P2_EXAMPLE);
 
         for (i, line) in input.iter().enumerate() {
-            let (res, _) = solve_one_p2(line);
-            assert_eq!(res, P2_ANSWERS[i]);
+            assert_eq!(solve_one(line, &p2_grammar()), P2_ANSWERS[i]);
         }
     }
 }
@@ -1,8 +1,6 @@
-#[macro_use]
-extern crate smallvec;
-
 use common::aoc::{load_input, print_result, print_time, run_many, run_once};
 use common::parsers::parse_usize;
+use rustc_hash::FxHashSet;
 use smallvec::SmallVec;
 
 fn main() {
@@ -49,97 +47,90 @@ fn parts_common(input: &Input, is_part2: bool) -> u32 {
     count
 }
 
+/// Checks the line against rule 0 with an Earley parser, which copes with any kind of
+/// recursion between the rules.
 fn check_line(line: &[u8], rules: &[Rule]) -> bool {
-    let mut stack: SmallVec<[(usize, usize, usize, bool); 64]> = smallvec![(0, 0, 0, false),];
-    let mut line_pos = 0;
-    let mut pass = true;
-
-    #[cfg(test)]
-    {
-        //println!("Line: {}", String::from_utf8(Vec::from(line)).unwrap());
-    }
+    let mut chart: Vec<Vec<Item>> = vec![Vec::with_capacity(16); line.len() + 1];
+    let mut seen = FxHashSet::default();
 
-    while stack.len() > 0 {
-        let (rule_index, pos, old_line_pos, right) = stack.pop().unwrap();
-        let rule = &rules[rule_index];
-
-        #[cfg(test)]
-        {
-            let d = b'E';
-            if stack.len() < 8 {
-                println!(
-                    "{}{} :: {:?} {} {} {}({}) {}",
-                    "  ".repeat(stack.len()),
-                    rule_index,
-                    rule,
-                    pos,
-                    right,
-                    line_pos,
-                    *line.get(line_pos).unwrap_or(&d) as char,
-                    pass
-                );
-            } else if stack.len() == 8 {
-                println!("                ...");
-            }
+    let mut add = |chart: &mut Vec<Vec<Item>>, pos: usize, item: Item| {
+        if seen.insert((pos, item)) {
+            chart[pos].push(item);
         }
+    };
 
-        match rule {
-            Rule::Character(b) => {
-                if line_pos == line.len() {
-                    pass = false;
-                } else {
-                    if line[line_pos] != *b {
-                        pass = false;
-                    }
+    for alt in 0..rules[0].alternatives().len() {
+        add(&mut chart, 0, Item::new(0, alt, 0));
+    }
 
-                    line_pos += 1;
-                }
-            }
-            Rule::SubRules(subs) => {
-                if pass {
-                    if pos < subs.len() {
-                        stack.push((rule_index, pos + 1, old_line_pos, right));
-                        stack.push((subs[pos], 0, line_pos, false));
-                    }
-                }
-            }
-            Rule::Disjunction(subs_left, subs_right) => {
-                if right {
-                    if pass {
-                        if pos < subs_right.len() {
-                            stack.push((rule_index, pos + 1, old_line_pos, true));
-                            stack.push((subs_right[pos], 0, line_pos, false));
+    for pos in 0..=line.len() {
+        let mut i = 0;
+        while i < chart[pos].len() {
+            let item = chart[pos][i];
+            let sequence = &rules[item.rule].alternatives()[item.alt];
+
+            if let Some(next) = sequence.get(item.dot) {
+                match &rules[*next] {
+                    Rule::Character(b) => {
+                        if line.get(pos) == Some(b) {
+                            add(&mut chart, pos + 1, item.advanced());
                         }
                     }
-                } else {
-                    if pass {
-                        if pos < subs_left.len() {
-                            stack.push((rule_index, pos + 1, old_line_pos, false));
-                            stack.push((subs_left[pos], 0, line_pos, false));
+                    Rule::Alternatives(alternatives) => {
+                        for alt in 0..alternatives.len() {
+                            add(&mut chart, pos, Item::new(*next, alt, pos));
                         }
-                    } else {
-                        pass = true;
-                        line_pos = old_line_pos;
-                        stack.push((rule_index, 0, old_line_pos, true));
                     }
+                    Rule::Nop => {}
+                }
+            } else {
+                // Every item waiting on this rule where it started can move past it.
+                let mut j = 0;
+                while j < chart[item.origin].len() {
+                    let parent = chart[item.origin][j];
+                    let parent_sequence = &rules[parent.rule].alternatives()[parent.alt];
+                    if parent_sequence.get(parent.dot) == Some(&item.rule) {
+                        add(&mut chart, pos, parent.advanced());
+                    }
+
+                    j += 1;
                 }
             }
-            Rule::Nop => {}
+
+            i += 1;
         }
     }
 
-    if pass && line_pos == line.len() {
-        #[cfg(test)]
-        {
-            println!("PASS Line: {}", String::from_utf8(Vec::from(line)).unwrap());
-        };
-        true
-    } else {
-        #[cfg(test)]
-        {
-            println!("FAIL Line: {}", String::from_utf8(Vec::from(line)).unwrap());
-        };
-        false
+    chart[line.len()].iter().any(|item| {
+        item.rule == 0 && item.origin == 0 && item.dot == rules[0].alternatives()[item.alt].len()
+    })
+}
+
+/// A partial match of an alternative of a rule, where everything before `dot` has been
+/// matched from `origin` up to the chart position the item is in.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+struct Item {
+    rule: usize,
+    alt: usize,
+    dot: usize,
+    origin: usize,
+}
+
+impl Item {
+    fn new(rule: usize, alt: usize, origin: usize) -> Item {
+        Item {
+            rule,
+            alt,
+            dot: 0,
+            origin,
+        }
+    }
+
+    fn advanced(self) -> Item {
+        Item {
+            dot: self.dot + 1,
+            ..self
+        }
     }
 }
 
@@ -147,7 +138,6 @@ fn check_line(line: &[u8], rules: &[Rule]) -> bool {
 struct Input {
     rules: Vec<Rule>,
     rules_p2: Vec<Rule>,
-    sub_indexes: Vec<usize>,
     data: Vec<u8>,
     slices: Vec<(usize, usize)>,
 }
@@ -158,118 +148,77 @@ impl Input {
     }
 
     fn parse(input: &str) -> Input {
-        let mut rules = vec![Rule::Nop; 64];
+        let mut rules = Vec::with_capacity(256);
         let mut data = Vec::with_capacity(64);
         let mut slices = Vec::with_capacity(64);
-        let mut sub_indexes = Vec::with_capacity(64);
 
         let mut parsed_rules = false;
 
         for line in input.lines() {
-            if line.len() == 0 {
+            if line.is_empty() {
                 parsed_rules = true;
                 continue;
             }
 
-            // The input is ascii, so using the same indexes is safe.
-            let bytes = line.as_bytes();
-
             if parsed_rules {
                 let start = data.len();
-                data.extend_from_slice(bytes);
+                data.extend_from_slice(line.as_bytes());
                 slices.push((start, data.len()));
             } else {
-                let colon_pos = line.find(":").unwrap();
-                let or_pos = line.find('|');
-                let quote_pos = line.find('"');
-
+                let colon_pos = line.find(':').unwrap();
                 let rule_index = parse_usize(&line[..colon_pos]);
-                while rules.len() <= rule_index {
-                    rules.push(Rule::Nop);
-                }
 
-                if let Some(quote_pos) = quote_pos {
-                    rules[rule_index] = Rule::Character(bytes[quote_pos + 1]);
-                } else if let Some(or_pos) = or_pos {
-                    rules[rule_index] = Rule::Disjunction(
-                        line[colon_pos + 2..or_pos - 1]
-                            .split(' ')
-                            .map(|t| parse_usize(t))
-                            .collect(),
-                        line[or_pos + 2..]
-                            .split(' ')
-                            .map(|t| parse_usize(t))
-                            .collect(),
-                    );
-                } else {
-                    rules[rule_index] = Rule::SubRules(
-                        line[colon_pos + 2..]
-                            .split(' ')
-                            .map(|t| parse_usize(t))
-                            .collect(),
-                    );
-
-                    sub_indexes.push(sub_indexes.len());
-                }
+                set_rule(&mut rules, rule_index, Rule::parse(&line[colon_pos + 2..]));
             }
         }
 
         let mut rules_p2 = rules.clone();
-        /* let mut r8_left = SmallVec::new();
-        let mut r8_right = SmallVec::new();
-        let mut r11_left = SmallVec::new();
-        let mut r11_right = SmallVec::new();
-        r8_left.push(42);
-        r8_right.push(42);
-        r8_right.push(8);
-        r11_left.push(42);
-        r11_left.push(31);
-        r11_right.push(42);
-        r11_right.push(11);
-        r11_right.push(31);
-        rules_p2[8] = Rule::Disjunction(true, r8_left, r8_right);
-        rules_p2[11] = Rule::Disjunction(true, r11_left, r11_right); */
-
-        rules_p2[0] = Rule::SubRules(smallvec![rules_p2.len()]);
-
-        for a in 0..6 {
-            for b in 0..6 {
-                let mut hax = SmallVec::new();
-                hax.push(42);
-                for _ in 0..a {
-                    hax.push(42);
-                }
-                hax.push(42);
-                for _ in 0..b {
-                    hax.push(42);
-                }
-                for _ in 0..b {
-                    hax.push(31);
-                }
-                hax.push(31);
-
-                rules_p2.push(Rule::Disjunction(hax, smallvec![rules_p2.len() + 1]));
-            }
-        }
-
-        rules_p2.push(Rule::Disjunction(smallvec![42], smallvec![42, 31]));
+        set_rule(&mut rules_p2, 8, Rule::parse("42 | 42 8"));
+        set_rule(&mut rules_p2, 11, Rule::parse("42 31 | 42 11 31"));
 
         Input {
             rules,
             rules_p2,
-            sub_indexes,
             data,
             slices,
         }
     }
 }
 
+fn set_rule(rules: &mut Vec<Rule>, index: usize, rule: Rule) {
+    if rules.len() <= index {
+        rules.resize(index + 1, Rule::Nop);
+    }
+
+    rules[index] = rule;
+}
+
 #[derive(Debug, Clone)]
 enum Rule {
     Nop,
     Character(u8),
-    SubRules(SmallVec<[usize; 8]>),
-    Disjunction(SmallVec<[usize; 8]>, SmallVec<[usize; 8]>),
+    Alternatives(SmallVec<[SmallVec<[usize; 4]>; 2]>),
+}
+
+impl Rule {
+    fn alternatives(&self) -> &[SmallVec<[usize; 4]>] {
+        match self {
+            Rule::Alternatives(alternatives) => alternatives,
+            _ => &[],
+        }
+    }
+
+    fn parse(s: &str) -> Rule {
+        if let Some(quote_pos) = s.find('"') {
+            return Rule::Character(s.as_bytes()[quote_pos + 1]);
+        }
+
+        Rule::Alternatives(
+            s.split(" | ")
+                .map(|alt| alt.split(' ').map(parse_usize).collect())
+                .collect(),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -346,5 +295,10 @@ aabbbbbaabbbaaaaaabbbbbababaaaaabbaaabba";
     #[test]
     fn test_part2() {
         assert_eq!(part2(&Input::parse(EXAMPLE_2)), 12);
+
+        // The same language, but left recursive and with more alternatives.
+        let mut input = Input::parse(EXAMPLE_2);
+        input.rules_p2[8] = Rule::parse("42 | 8 42 | 42 42 8");
+        assert_eq!(part2(&input), 12);
     }
 }
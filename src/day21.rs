@@ -1,6 +1,7 @@
-#![feature(is_sorted)]
-
 use common::aoc::{load_input, print_result, print_time, run_many, run_once};
+use common::bitset::{BitSet, HeapBitSet};
+use common::intern::Interner;
+use common::solve::solve;
 use smallvec::SmallVec;
 
 fn main() {
@@ -29,21 +30,21 @@ fn main() {
 
 fn part1(input: &Input) -> (u64, Vec<usize>) {
     let mut result = 0;
-    let total_mask = (1 << input.allergens.len()) - 1;
+    let total_mask = HeapBitSet::full(input.allergens.len());
     let mut inerts = Vec::with_capacity(16);
 
     for i in 0..input.ingredients.len() {
         let mut count = 0;
-        let mut mask = total_mask;
+        let mut mask = total_mask.clone();
         for food in input.foods.iter() {
             if food.contains_ingredient(i) {
                 count += 1;
             } else {
-                mask &= !food.allergen_mask;
+                mask.difference_with(&food.allergen_mask);
             }
         }
 
-        if mask == 0 {
+        if mask.is_empty() {
             result += count;
             inerts.push(i);
         }
@@ -70,51 +71,22 @@ fn part2(input: &Input, inerts: &[usize]) -> String {
     }
 
     // Identify allergens
-    let mut bad_ingredients = vec![0; not_inerts.len()];
-    let mut has_found_baddie = vec![false; not_inerts.len()];
-    let mut baddie_count = 0;
-    let mut has_found_ingredient = vec![false; not_inerts.len()];
-
-    while baddie_count < not_inerts.len() {
-        for (i, allergen_index) in input.allergen_order.iter().cloned().enumerate() {
-            if has_found_baddie[i] {
-                continue;
-            }
-
-            let allergen_mask = 1 << allergen_index;
-            let mut candidates: SmallVec<[usize; 4]> = SmallVec::default();
-
-            for (j, ingredient_index) in not_inerts.iter().cloned().enumerate() {
-                if has_found_ingredient[j] {
-                    continue;
-                }
-
-                let mut failed = false;
-                for food in input.foods.iter() {
-                    if !food.contains_ingredient(ingredient_index) {
-                        if food.allergen_mask & allergen_mask == allergen_mask {
-                            failed = true;
-                            break;
-                        }
-                    }
-                }
-
-                if !failed {
-                    candidates.push(j);
-                }
-            }
-
-            if candidates.len() == 1 {
-                let c = candidates[0];
-
-                bad_ingredients[i] = not_inerts[c];
-                has_found_ingredient[c] = true;
-                has_found_baddie[i] = true;
-                baddie_count += 1;
-            }
+    let mut candidates = vec![vec![false; not_inerts.len()]; input.allergens.len()];
+    for (allergen_index, candidates) in candidates.iter_mut().enumerate() {
+        for (j, ingredient_index) in not_inerts.iter().cloned().enumerate() {
+            candidates[j] = input.foods.iter().all(|food| {
+                food.contains_ingredient(ingredient_index)
+                    || !food.allergen_mask.contains(allergen_index)
+            });
         }
     }
 
+    let bad_ingredients: Vec<usize> = solve(&candidates, not_inerts.len())
+        .unwrap()
+        .into_iter()
+        .map(|j| not_inerts[j])
+        .collect();
+
     // Assemble string
     let mut res = String::with_capacity(16 + not_inerts.len() * 8);
     for (i, bad_ingredient) in bad_ingredients.iter().enumerate() {
@@ -122,25 +94,23 @@ fn part2(input: &Input, inerts: &[usize]) -> String {
             res.push(',');
         }
 
-        res.push_str(input.ingredients[*bad_ingredient]);
+        res.push_str(input.ingredients.name(*bad_ingredient as u32));
     }
 
     res
 }
 
-struct Input<'a> {
-    allergens: Vec<&'a str>,
-    ingredients: Vec<&'a str>,
-    allergen_order: Vec<usize>,
+struct Input {
+    allergens: Interner,
+    ingredients: Interner,
     foods: Vec<Food>,
 }
 
-impl<'a> Input<'a> {
-    fn parse(input_str: &'a str) -> Input {
+impl Input {
+    fn parse(input_str: &str) -> Input {
         let mut input = Input {
-            allergens: Vec::with_capacity(64),
-            ingredients: Vec::with_capacity(256),
-            allergen_order: Vec::new(),
+            allergens: Interner::with_capacity(64),
+            ingredients: Interner::with_capacity(256),
             foods: Vec::with_capacity(64),
         };
 
@@ -149,67 +119,37 @@ impl<'a> Input<'a> {
                 continue;
             }
 
-            if line.ends_with(')') {
-                let para_pos = line.find('(').unwrap();
-
-                let mut ingredients = SmallVec::new();
-                for ingredient in line[..para_pos - 1].split(' ') {
-                    let ingredient_index = if let Some(index) =
-                        input.ingredients.iter().position(|p| *p == ingredient)
-                    {
-                        index
-                    } else {
-                        input.ingredients.push(ingredient);
-                        input.ingredients.len() - 1
-                    };
-
-                    ingredients.push(ingredient_index);
-                }
+            let para_pos = line.find('(');
+            let ingredients_end = para_pos.map(|p| p - 1).unwrap_or(line.len());
+
+            let ingredients = line[..ingredients_end]
+                .split(' ')
+                .map(|ingredient| input.ingredients.intern(ingredient) as usize)
+                .collect();
 
-                let mut allergen_mask = 0u64;
+            let mut allergen_mask = HeapBitSet::new();
+            if let Some(para_pos) = para_pos {
                 for allergen in line[para_pos + 10..line.len() - 1].split(' ') {
                     let allergen = allergen.trim_end_matches(',');
-                    let allergen_index =
-                        if let Some(index) = input.allergens.iter().position(|p| *p == allergen) {
-                            index
-                        } else {
-                            input.allergens.push(allergen);
-                            input.allergens.len() - 1
-                        };
-
-                    allergen_mask |= 1 << allergen_index;
-                }
-
-                input.foods.push(Food {
-                    ingredients,
-                    allergen_mask,
-                });
-            } else {
-                let mut ingredients = SmallVec::new();
-                for ingredient in line.split(' ') {
-                    let ingredient_index = if let Some(index) =
-                        input.ingredients.iter().position(|p| *p == ingredient)
-                    {
-                        index
-                    } else {
-                        input.ingredients.push(ingredient);
-                        input.ingredients.len() - 1
-                    };
-
-                    ingredients.push(ingredient_index);
+                    allergen_mask.insert(input.allergens.intern(allergen) as usize);
                 }
-
-                input.foods.push(Food {
-                    ingredients,
-                    allergen_mask: 0,
-                });
             }
-        }
 
-        let mut allergen_order: Vec<usize> = (0..input.allergens.len()).collect();
-        allergen_order.sort_by(|a, b| input.allergens[*a].cmp(input.allergens[*b]));
+            input.foods.push(Food {
+                ingredients,
+                allergen_mask,
+            });
+        }
 
-        input.allergen_order = allergen_order;
+        // The dangerous ingredient list is ordered by allergen name.
+        let remap = input.allergens.sort();
+        for food in input.foods.iter_mut() {
+            food.allergen_mask = food
+                .allergen_mask
+                .iter()
+                .map(|a| remap[a] as usize)
+                .collect();
+        }
 
         input
     }
@@ -217,7 +157,7 @@ impl<'a> Input<'a> {
 
 struct Food {
     ingredients: SmallVec<[usize; 16]>,
-    allergen_mask: u64,
+    allergen_mask: HeapBitSet,
 }
 
 impl Food {
@@ -1,7 +1,7 @@
 use common::aoc::{load_input, print_result, print_time, run_many, run_once};
+use common::math::{discrete_log, mod_pow};
 use common::parsers::parse_u64;
 
-const START_VALUE: u64 = 1;
 const SUBJECT_NUMBER: u64 = 7;
 const MODULO: u64 = 20201227;
 
@@ -38,24 +38,11 @@ fn part1(card_public_key: u64, door_public_key: u64) -> u64 {
 }
 
 fn find_loop_size(public_key: u64) -> u64 {
-    let mut loop_size = 0;
-    let mut value = START_VALUE;
-
-    while value != public_key {
-        value = (value * SUBJECT_NUMBER) % MODULO;
-        loop_size += 1;
-    }
-
-    loop_size
+    discrete_log(SUBJECT_NUMBER, public_key, MODULO).unwrap()
 }
 
 fn transform_key(public_key: u64, loop_size: u64) -> u64 {
-    let mut value = 1;
-    for _ in 0..loop_size {
-        value = (value * public_key) % MODULO;
-    }
-
-    value
+    mod_pow(public_key, loop_size, MODULO)
 }
 
 fn parse_input(input: &str) -> (u64, u64) {
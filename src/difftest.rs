@@ -0,0 +1,562 @@
+use crate::coprocessor::{
+    Compiled, CompiledProgram, Instruction, Optimized, OptimizedProgram, Program,
+};
+use crate::random::Rng;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Snapshot {
+    pub registers: [i64; 26],
+    pub pc: usize,
+    pub muls: u64,
+}
+
+/// A coprocessor implementation that can be compared against the others.
+pub trait Backend {
+    fn name(&self) -> &str;
+    /// Runs one step, returning whether the program has ended.
+    fn step(&mut self) -> bool;
+    fn snapshot(&self) -> Snapshot;
+}
+
+impl<'a> Backend for Program<'a> {
+    fn name(&self) -> &str {
+        "interpreter"
+    }
+
+    fn step(&mut self) -> bool {
+        self.run()
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: self.registers,
+            pc: self.pc,
+            muls: self.muls,
+        }
+    }
+}
+
+impl<'a> Backend for CompiledProgram<'a> {
+    fn name(&self) -> &str {
+        "compiled"
+    }
+
+    fn step(&mut self) -> bool {
+        self.run()
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: self.registers,
+            pc: self.pc,
+            muls: self.muls,
+        }
+    }
+}
+
+impl<'a> Backend for OptimizedProgram<'a> {
+    fn name(&self) -> &str {
+        "optimized"
+    }
+
+    fn step(&mut self) -> bool {
+        self.run()
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: self.registers,
+            pc: self.pc,
+            muls: self.muls,
+        }
+    }
+}
+
+/// A deliberately naive evaluator written straight from the puzzle text, which the other
+/// backends are checked against.
+pub struct Reference<'a> {
+    instructions: &'a [Instruction],
+    state: Snapshot,
+}
+
+impl<'a> Reference<'a> {
+    pub fn new(instructions: &'a [Instruction]) -> Reference<'a> {
+        Reference {
+            instructions,
+            state: Snapshot {
+                registers: [0; 26],
+                pc: 0,
+                muls: 0,
+            },
+        }
+    }
+}
+
+impl<'a> Backend for Reference<'a> {
+    fn name(&self) -> &str {
+        "reference"
+    }
+
+    fn step(&mut self) -> bool {
+        let state = &mut self.state;
+        let (x, y, op) = match self.instructions[state.pc] {
+            Instruction::SetN(r, n) => (Some(r), n, "set"),
+            Instruction::SetR(r1, r2) => (Some(r1), state.registers[r2], "set"),
+            Instruction::SubN(r, n) => (Some(r), n, "sub"),
+            Instruction::SubR(r1, r2) => (Some(r1), state.registers[r2], "sub"),
+            Instruction::MulN(r, n) => (Some(r), n, "mul"),
+            Instruction::MulR(r1, r2) => (Some(r1), state.registers[r2], "mul"),
+            Instruction::Jnz(r, n) => (Some(r), n, "jnz"),
+            Instruction::Jnz1(n) => (None, n, "jnz"),
+        };
+        let x_value = x.map(|r| state.registers[r]).unwrap_or(1);
+
+        let mut offset = 1;
+        match op {
+            "set" => state.registers[x.unwrap()] = y,
+            "sub" => state.registers[x.unwrap()] = x_value.wrapping_sub(y),
+            "mul" => {
+                state.registers[x.unwrap()] = x_value.wrapping_mul(y);
+                state.muls += 1;
+            }
+            _ => {
+                if x_value != 0 {
+                    offset = y;
+                }
+            }
+        }
+        state.pc = (state.pc as i64).wrapping_add(offset) as usize;
+
+        state.pc == self.instructions.len()
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        self.state
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Divergence {
+    pub backend: String,
+    pub step: usize,
+    pub instruction: Option<Instruction>,
+    pub expected: Snapshot,
+    pub actual: Snapshot,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} diverged at step {}", self.backend, self.step)?;
+        if let Some(instruction) = self.instruction {
+            write!(f, " after `{}`", instruction)?;
+        }
+        writeln!(f)?;
+
+        if self.expected.pc != self.actual.pc {
+            writeln!(
+                f,
+                "  pc: expected {}, got {}",
+                self.expected.pc, self.actual.pc
+            )?;
+        }
+        if self.expected.muls != self.actual.muls {
+            writeln!(
+                f,
+                "  muls: expected {}, got {}",
+                self.expected.muls, self.actual.muls
+            )?;
+        }
+        for r in 0..26 {
+            if self.expected.registers[r] != self.actual.registers[r] {
+                writeln!(
+                    f,
+                    "  {}: expected {}, got {}",
+                    (b'a' + r as u8) as char,
+                    self.expected.registers[r],
+                    self.actual.registers[r]
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Steps every backend in lockstep with the first one for up to `max_steps` steps and
+/// returns the number of steps taken, or the first state that differs.
+pub fn compare_steps(
+    instructions: &[Instruction],
+    backends: &mut [&mut dyn Backend],
+    max_steps: usize,
+) -> Result<usize, Box<Divergence>> {
+    let (expected, others) = backends.split_first_mut().unwrap();
+
+    for step in 0..max_steps {
+        let instruction = instructions.get(expected.snapshot().pc).copied();
+        let ended = expected.step();
+        let expected_state = expected.snapshot();
+
+        for backend in others.iter_mut() {
+            let backend_ended = backend.step();
+            let actual = backend.snapshot();
+
+            if actual != expected_state || backend_ended != ended {
+                return Err(Box::new(Divergence {
+                    backend: backend.name().to_owned(),
+                    step,
+                    instruction,
+                    expected: expected_state,
+                    actual,
+                }));
+            }
+        }
+
+        if ended {
+            return Ok(step + 1);
+        }
+    }
+
+    Ok(max_steps)
+}
+
+/// Runs both backends to the end and compares where they stopped, for backends that don't
+/// take the same steps. Returns `Ok(false)` if `expected` doesn't end within `max_steps`.
+pub fn compare_final(
+    expected: &mut dyn Backend,
+    actual: &mut dyn Backend,
+    max_steps: usize,
+) -> Result<bool, Box<Divergence>> {
+    if !run_to_end(expected, max_steps) {
+        return Ok(false);
+    }
+    let ended = run_to_end(actual, max_steps);
+
+    let expected = expected.snapshot();
+    let snapshot = actual.snapshot();
+    if !ended || snapshot != expected {
+        return Err(Box::new(Divergence {
+            backend: actual.name().to_owned(),
+            step: max_steps,
+            instruction: None,
+            expected,
+            actual: snapshot,
+        }));
+    }
+
+    Ok(true)
+}
+
+fn run_to_end(backend: &mut dyn Backend, max_steps: usize) -> bool {
+    for _ in 0..max_steps {
+        if backend.step() {
+            return true;
+        }
+    }
+
+    false
+}
+
+fn random_arithmetic(rng: &mut Rng, registers: usize) -> Instruction {
+    let r1 = rng.below(registers as u64) as usize;
+    let r2 = rng.below(registers as u64) as usize;
+    let n = rng.range(-9, 9);
+
+    match rng.below(6) {
+        0 => Instruction::SetN(r1, n),
+        1 => Instruction::SetR(r1, r2),
+        2 => Instruction::SubN(r1, n),
+        3 => Instruction::SubR(r1, r2),
+        4 => Instruction::MulN(r1, n),
+        _ => Instruction::MulR(r1, r2),
+    }
+}
+
+pub fn random_straight_line(rng: &mut Rng, len: usize, registers: usize) -> Vec<Instruction> {
+    (0..len)
+        .map(|_| random_arithmetic(rng, registers))
+        .collect()
+}
+
+/// A random program where every jump lands inside the program or right at its end.
+pub fn random_looping(rng: &mut Rng, len: usize, registers: usize) -> Vec<Instruction> {
+    (0..len)
+        .map(|pc| {
+            if rng.below(4) != 0 {
+                return random_arithmetic(rng, registers);
+            }
+
+            let offset = rng.range(-(pc as i64), (len - pc) as i64);
+            if rng.below(3) == 0 {
+                Instruction::Jnz1(offset)
+            } else {
+                Instruction::Jnz(rng.below(registers as u64) as usize, offset)
+            }
+        })
+        .collect()
+}
+
+/// `n` different registers below `registers`.
+fn distinct_registers(rng: &mut Rng, n: usize, registers: usize) -> Vec<usize> {
+    let mut chosen = Vec::with_capacity(n);
+    while chosen.len() < n {
+        let r = rng.below(registers as u64) as usize;
+        if !chosen.contains(&r) {
+            chosen.push(r);
+        }
+    }
+
+    chosen
+}
+
+/// A nonzero step and a number of passes, for loops that stop where they should.
+fn random_step(rng: &mut Rng) -> (i64, i64) {
+    let step = match rng.range(-3, 2) {
+        0 => 3,
+        step => step,
+    };
+
+    (step, rng.range(1, 6))
+}
+
+/// `set g x; sub g y` with `x` and `y` in either order, which is zero when they're equal.
+fn compare(rng: &mut Rng, g: usize, x: usize, y: usize) -> [Instruction; 2] {
+    if rng.below(2) == 0 {
+        [Instruction::SetR(g, x), Instruction::SubR(g, y)]
+    } else {
+        [Instruction::SetR(g, y), Instruction::SubR(g, x)]
+    }
+}
+
+/// The inner loop of the composite check: `f` is cleared if `d * e == b` as `e` steps up
+/// to `b`.
+fn factor_loop(rng: &mut Rng, [b, d, e, f, g]: [usize; 5], step: i64) -> Vec<Instruction> {
+    let [set, mul] = if rng.below(2) == 0 {
+        [Instruction::SetR(g, d), Instruction::MulR(g, e)]
+    } else {
+        [Instruction::SetR(g, e), Instruction::MulR(g, d)]
+    };
+
+    let mut code = vec![
+        set,
+        mul,
+        Instruction::SubR(g, b),
+        Instruction::Jnz(g, 2),
+        Instruction::SetN(f, 0),
+        Instruction::SubN(e, -step),
+    ];
+    code.extend_from_slice(&compare(rng, g, e, b));
+    code.push(Instruction::Jnz(g, -8));
+
+    code
+}
+
+/// A random program around one of the loops that the optimizer turns into a builtin: a
+/// factor search, a composite check, or a multiplication by repeated subtraction. Now and
+/// then the loop doesn't reach its end condition, so the optimizer has to fall back.
+pub fn random_idiom(rng: &mut Rng, registers: usize) -> Vec<Instruction> {
+    let len = rng.below(4) as usize;
+    let mut code = random_straight_line(rng, len, registers);
+    let chosen = distinct_registers(rng, 5, registers);
+    let [b, d, e, f, g] = [chosen[0], chosen[1], chosen[2], chosen[3], chosen[4]];
+    let miss = if rng.below(8) == 0 { 1 } else { 0 };
+
+    match rng.below(3) {
+        0 => {
+            let (step, passes) = random_step(rng);
+            let e0 = rng.range(-12, 12);
+            code.extend_from_slice(&[
+                Instruction::SetN(b, e0 + step * passes + miss),
+                Instruction::SetN(d, rng.range(-6, 6)),
+                Instruction::SetN(e, e0),
+                Instruction::SetN(f, 1),
+            ]);
+            code.extend(factor_loop(rng, [b, d, e, f, g], step));
+        }
+        1 => {
+            let (d_step, d_passes) = random_step(rng);
+            let (e_step, e_passes) = random_step(rng);
+            let e0 = rng.range(-12, 12);
+            let b_value = e0 + e_step * e_passes;
+            code.extend_from_slice(&[
+                Instruction::SetN(b, b_value),
+                Instruction::SetN(f, 1),
+                Instruction::SetN(d, b_value - d_step * d_passes + miss),
+                Instruction::SetN(e, e0),
+            ]);
+            code.extend(factor_loop(rng, [b, d, e, f, g], e_step));
+            code.push(Instruction::SubN(d, -d_step));
+            code.extend_from_slice(&compare(rng, g, d, b));
+            code.push(Instruction::Jnz(g, -13));
+        }
+        _ => {
+            // `d` counts down, `e` is left alone, and the rest are subtracted from.
+            let (step, passes) = random_step(rng);
+            code.push(Instruction::SetN(d, -step * passes + miss));
+
+            let mut body = vec![Instruction::SubN(d, -step)];
+            for _ in 0..rng.range(0, 3) {
+                let r = [b, f, g][rng.below(3) as usize];
+                let update = if rng.below(2) == 0 {
+                    Instruction::SubN(r, rng.range(-9, 9))
+                } else {
+                    Instruction::SubR(r, e)
+                };
+                let at = rng.below(body.len() as u64 + 1) as usize;
+                body.insert(at, update);
+            }
+            body.push(Instruction::Jnz(d, -(body.len() as i64)));
+            code.extend(body);
+        }
+    }
+
+    let len = rng.below(3) as usize;
+    code.extend(random_straight_line(rng, len, registers));
+    code
+}
+
+/// Checks the interpreter and the compiled backend step by step against the reference, and
+/// the optimized backend's final state, on `count` random programs of each kind.
+pub fn fuzz(
+    seed: u64,
+    count: usize,
+    max_steps: usize,
+) -> Result<(), (Vec<Instruction>, Box<Divergence>)> {
+    let mut rng = Rng::new(seed);
+
+    for i in 0..count * 3 {
+        let len = 1 + rng.below(24) as usize;
+        let instructions = match i % 3 {
+            0 => random_straight_line(&mut rng, len, 8),
+            1 => random_looping(&mut rng, len, 8),
+            _ => random_idiom(&mut rng, 8),
+        };
+
+        let compiled = Compiled::compile(&instructions);
+        let optimized = Optimized::compile(&instructions);
+
+        let mut reference = Reference::new(&instructions);
+        let mut program = Program::from(&instructions);
+        let mut compiled_program = compiled.program();
+        let result = compare_steps(
+            &instructions,
+            &mut [&mut reference, &mut program, &mut compiled_program],
+            max_steps,
+        )
+        .and_then(|_| {
+            let mut reference = Reference::new(&instructions);
+            let mut optimized_program = optimized.program();
+            compare_final(&mut reference, &mut optimized_program, max_steps)
+        });
+
+        if let Err(divergence) = result {
+            return Err((instructions, divergence));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coprocessor::Op;
+
+    /// The interpreter as it was before `mul X N` stopped adding instead.
+    struct AddingMul<'a>(Program<'a>, &'a [Instruction]);
+
+    impl<'a> Backend for AddingMul<'a> {
+        fn name(&self) -> &str {
+            "adding-mul"
+        }
+
+        fn step(&mut self) -> bool {
+            if let Instruction::MulN(r, n) = self.1[self.0.pc] {
+                self.0.registers[r] += n;
+                self.0.pc += 1;
+                self.0.muls += 1;
+                self.0.pc == self.1.len()
+            } else {
+                self.0.run()
+            }
+        }
+
+        fn snapshot(&self) -> Snapshot {
+            self.0.snapshot()
+        }
+    }
+
+    #[test]
+    fn test_fuzz() {
+        for seed in 1..=20 {
+            if let Err((instructions, divergence)) = fuzz(seed, 50, 1000) {
+                panic!("{:?}\n{}", instructions, divergence);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generates_idioms() {
+        let mut rng = Rng::new(3);
+        let mut seen = [false; 3];
+
+        for _ in 0..100 {
+            let instructions = random_idiom(&mut rng, 8);
+            let optimized = Optimized::compile(&instructions);
+            let mut found = false;
+            for op in optimized.ops() {
+                let kind = match op {
+                    Op::FindFactor { .. } => 0,
+                    Op::CheckComposite { .. } => 1,
+                    Op::MulLoop { .. } => 2,
+                    _ => continue,
+                };
+                seen[kind] = true;
+                found = true;
+            }
+
+            assert!(found, "no idiom in {:?}", instructions);
+        }
+
+        assert_eq!(seen, [true; 3]);
+    }
+
+    #[test]
+    fn test_reports_first_divergence() {
+        let instructions = vec![
+            Instruction::SetN(1, 3),
+            Instruction::SubN(1, -2),
+            Instruction::MulN(1, 4),
+            Instruction::MulN(1, 4),
+        ];
+
+        let mut reference = Reference::new(&instructions);
+        let mut buggy = AddingMul(Program::from(&instructions), &instructions);
+        let divergence =
+            compare_steps(&instructions, &mut [&mut reference, &mut buggy], 10).unwrap_err();
+
+        assert_eq!(divergence.backend, "adding-mul");
+        assert_eq!(divergence.step, 2);
+        assert_eq!(divergence.instruction, Some(Instruction::MulN(1, 4)));
+        assert_eq!(divergence.expected.registers[1], 20);
+        assert_eq!(divergence.actual.registers[1], 9);
+        assert_eq!(
+            divergence.to_string(),
+            "adding-mul diverged at step 2 after `mul b 4`\n  b: expected 20, got 9\n"
+        );
+    }
+
+    #[test]
+    fn test_finds_adding_mul() {
+        let mut rng = Rng::new(7);
+        let found = (0..100).any(|_| {
+            let instructions = random_straight_line(&mut rng, 16, 4);
+            let mut reference = Reference::new(&instructions);
+            let mut buggy = AddingMul(Program::from(&instructions), &instructions);
+
+            compare_steps(&instructions, &mut [&mut reference, &mut buggy], 100).is_err()
+        });
+
+        assert!(found);
+    }
+}
@@ -0,0 +1,305 @@
+use num::traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub};
+use num::Zero;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Op {
+    fn symbol(self) -> char {
+        match self {
+            Op::Add => '+',
+            Op::Sub => '-',
+            Op::Mul => '*',
+            Op::Div => '/',
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+/// Which operators an expression may use and how tightly they bind. A higher precedence
+/// binds tighter, and operators missing from `binary` are rejected by the parser.
+#[derive(Clone, Debug)]
+pub struct Grammar {
+    pub binary: Vec<(Op, u32, Assoc)>,
+    pub unary_minus: Option<u32>,
+}
+
+impl Grammar {
+    /// The usual rules, with `*` and `/` above `+` and `-`, and unary minus above both.
+    pub fn arithmetic() -> Grammar {
+        Grammar {
+            binary: vec![
+                (Op::Add, 1, Assoc::Left),
+                (Op::Sub, 1, Assoc::Left),
+                (Op::Mul, 2, Assoc::Left),
+                (Op::Div, 2, Assoc::Left),
+            ],
+            unary_minus: Some(3),
+        }
+    }
+
+    fn binding_power(&self, op: Op) -> Option<(u32, u32)> {
+        let (_, precedence, assoc) = self.binary.iter().find(|(o, _, _)| *o == op)?;
+
+        Some(match assoc {
+            Assoc::Left => (precedence * 2, precedence * 2 + 1),
+            Assoc::Right => (precedence * 2 + 1, precedence * 2),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Token {
+    Number(i64),
+    Op(Op),
+    Open,
+    Close,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    /// A character that isn't part of any token, with its byte offset.
+    UnexpectedChar(usize, char),
+    /// A number at the given byte offset that doesn't fit in an `i64`.
+    NumberTooLarge(usize),
+    /// A token that can't go where it is, with its index.
+    UnexpectedToken(usize),
+    UnexpectedEnd,
+    /// An operator that isn't in the grammar.
+    UnknownOperator(Op),
+}
+
+pub fn tokenize(s: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::with_capacity(s.len() / 2);
+    let bytes = s.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let token = match bytes[i] {
+            b' ' | b'\t' => None,
+            b'+' => Some(Token::Op(Op::Add)),
+            b'-' => Some(Token::Op(Op::Sub)),
+            b'*' => Some(Token::Op(Op::Mul)),
+            b'/' => Some(Token::Op(Op::Div)),
+            b'(' => Some(Token::Open),
+            b')' => Some(Token::Close),
+            b'0'..=b'9' => {
+                let start = i;
+                let mut n = 0i64;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    n = n
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add((bytes[i] - b'0') as i64))
+                        .ok_or(ParseError::NumberTooLarge(start))?;
+                    i += 1;
+                }
+
+                tokens.push(Token::Number(n));
+                continue;
+            }
+            _ => {
+                let c = s[i..].chars().next().unwrap();
+                return Err(ParseError::UnexpectedChar(i, c));
+            }
+        };
+
+        if let Some(token) = token {
+            tokens.push(token);
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Evaluates the expression, or returns `None` on division by zero or if a value
+    /// doesn't fit in `T`. Division rounds towards zero.
+    pub fn eval<T>(&self) -> Option<T>
+    where
+        T: From<i64> + Zero + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv,
+    {
+        match self {
+            Expr::Number(n) => Some(T::from(*n)),
+            Expr::Neg(expr) => T::zero().checked_sub(&expr.eval()?),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs: T = lhs.eval()?;
+                let rhs: T = rhs.eval()?;
+
+                match op {
+                    Op::Add => lhs.checked_add(&rhs),
+                    Op::Sub => lhs.checked_sub(&rhs),
+                    Op::Mul => lhs.checked_mul(&rhs),
+                    Op::Div => lhs.checked_div(&rhs),
+                }
+            }
+        }
+    }
+
+    pub fn parse(s: &str, grammar: &Grammar) -> Result<Expr, ParseError> {
+        Expr::from_tokens(&tokenize(s)?, grammar)
+    }
+
+    pub fn from_tokens(tokens: &[Token], grammar: &Grammar) -> Result<Expr, ParseError> {
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            grammar,
+        };
+
+        let expr = parser.expr(0)?;
+        if parser.pos < tokens.len() {
+            return Err(ParseError::UnexpectedToken(parser.pos));
+        }
+
+        Ok(expr)
+    }
+}
+
+/// Fully parenthesized, so the structure of the tree is visible.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Neg(expr) => write!(f, "(-{})", expr),
+            Expr::Binary(op, lhs, rhs) => write!(f, "({} {} {})", lhs, op.symbol(), rhs),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    grammar: &'a Grammar,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<Token, ParseError> {
+        let token = self.tokens.get(self.pos).ok_or(ParseError::UnexpectedEnd)?;
+        self.pos += 1;
+
+        Ok(*token)
+    }
+
+    /// Parses operators as long as they bind at least as tightly as `min_power`.
+    fn expr(&mut self, min_power: u32) -> Result<Expr, ParseError> {
+        let mut lhs = match self.next()? {
+            Token::Number(n) => Expr::Number(n),
+            Token::Open => {
+                let expr = self.expr(0)?;
+                if self.next()? != Token::Close {
+                    return Err(ParseError::UnexpectedToken(self.pos - 1));
+                }
+
+                expr
+            }
+            Token::Op(Op::Sub) => match self.grammar.unary_minus {
+                Some(precedence) => Expr::Neg(Box::new(self.expr(precedence * 2 + 1)?)),
+                None => return Err(ParseError::UnexpectedToken(self.pos - 1)),
+            },
+            _ => return Err(ParseError::UnexpectedToken(self.pos - 1)),
+        };
+
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos).cloned() {
+            let (left_power, right_power) = self
+                .grammar
+                .binding_power(op)
+                .ok_or(ParseError::UnknownOperator(op))?;
+            if left_power < min_power {
+                break;
+            }
+
+            self.pos += 1;
+            let rhs = self.expr(right_power)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::BigInt;
+
+    fn parse(s: &str, grammar: &Grammar) -> String {
+        Expr::parse(s, grammar).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_precedence() {
+        let arithmetic = Grammar::arithmetic();
+        assert_eq!(parse("1 + 2 * 3 - 4", &arithmetic), "((1 + (2 * 3)) - 4)");
+        assert_eq!(parse("-2 * -(3 + 4)", &arithmetic), "((-2) * (-(3 + 4)))");
+        assert_eq!(parse("8 / 4 / 2", &arithmetic), "((8 / 4) / 2)");
+
+        let right = Grammar {
+            binary: vec![(Op::Div, 1, Assoc::Right), (Op::Add, 2, Assoc::Left)],
+            unary_minus: None,
+        };
+        assert_eq!(parse("8 / 4 / 2 + 1", &right), "(8 / (4 / (2 + 1)))");
+        assert_eq!(
+            Expr::parse("8 * 2", &right),
+            Err(ParseError::UnknownOperator(Op::Mul))
+        );
+        assert_eq!(
+            Expr::parse("-8", &right),
+            Err(ParseError::UnexpectedToken(0))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let arithmetic = Grammar::arithmetic();
+        assert_eq!(
+            Expr::parse("(1 + 2", &arithmetic),
+            Err(ParseError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Expr::parse("1 + 2)", &arithmetic),
+            Err(ParseError::UnexpectedToken(3))
+        );
+        assert_eq!(
+            Expr::parse("1 % 2", &arithmetic),
+            Err(ParseError::UnexpectedChar(2, '%'))
+        );
+        assert_eq!(
+            tokenize("99999999999999999999"),
+            Err(ParseError::NumberTooLarge(0))
+        );
+    }
+
+    #[test]
+    fn test_eval() {
+        let arithmetic = Grammar::arithmetic();
+        let eval = |s| Expr::parse(s, &arithmetic).unwrap().eval::<i64>();
+
+        assert_eq!(eval("7 - 10 / 3 * 2"), Some(1));
+        assert_eq!(eval("-7 / 2"), Some(-3));
+        assert_eq!(eval("1 / (2 - 2)"), None);
+        assert_eq!(eval("3037000500 * 3037000500"), None);
+
+        let big = Expr::parse("3037000500 * 3037000500 * -2", &arithmetic).unwrap();
+        assert_eq!(big.eval::<i128>(), Some(-18446744074000500000));
+        assert_eq!(big.eval::<BigInt>(), "-18446744074000500000".parse().ok());
+    }
+}
@@ -0,0 +1,286 @@
+use crate::intern::Interner;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// The nodes of a cycle, where the last one leads back to the first.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cycle(pub Vec<usize>);
+
+/// A directed graph with weighted edges, where nodes are created from names on first use
+/// and referred to by their index afterwards.
+#[derive(Clone, Debug)]
+pub struct Graph<W> {
+    names: Interner,
+    edges: Vec<Vec<(usize, W)>>,
+    reverse_edges: Vec<Vec<(usize, W)>>,
+}
+
+impl<W: Copy> Graph<W> {
+    pub fn new() -> Graph<W> {
+        Graph {
+            names: Interner::new(),
+            edges: Vec::new(),
+            reverse_edges: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn node(&mut self, name: &str) -> usize {
+        let index = self.names.intern(name) as usize;
+        if index == self.edges.len() {
+            self.edges.push(Vec::new());
+            self.reverse_edges.push(Vec::new());
+        }
+
+        index
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.names.get(name).map(|id| id as usize)
+    }
+
+    pub fn name(&self, node: usize) -> &str {
+        self.names.name(node as u32)
+    }
+
+    pub fn add_edge(&mut self, from: usize, to: usize, weight: W) {
+        self.edges[from].push((to, weight));
+        self.reverse_edges[to].push((from, weight));
+    }
+
+    pub fn edges(&self, node: usize, direction: Direction) -> &[(usize, W)] {
+        match direction {
+            Direction::Forward => &self.edges[node],
+            Direction::Backward => &self.reverse_edges[node],
+        }
+    }
+
+    /// The nodes reachable from `start`, including itself, in breadth-first order.
+    pub fn bfs(&self, start: usize, direction: Direction) -> Vec<usize> {
+        let mut seen = vec![false; self.len()];
+        let mut order = Vec::with_capacity(64);
+
+        seen[start] = true;
+        order.push(start);
+
+        let mut i = 0;
+        while i < order.len() {
+            for (next, _) in self.edges(order[i], direction).iter() {
+                if !seen[*next] {
+                    seen[*next] = true;
+                    order.push(*next);
+                }
+            }
+
+            i += 1;
+        }
+
+        order
+    }
+
+    /// The nodes reachable from `start`, including itself, in depth-first pre-order.
+    pub fn dfs(&self, start: usize, direction: Direction) -> Vec<usize> {
+        let mut seen = vec![false; self.len()];
+        let mut order = Vec::with_capacity(64);
+        let mut stack = vec![start];
+
+        while let Some(node) = stack.pop() {
+            if seen[node] {
+                continue;
+            }
+            seen[node] = true;
+            order.push(node);
+
+            for (next, _) in self.edges(node, direction).iter().rev() {
+                if !seen[*next] {
+                    stack.push(*next);
+                }
+            }
+        }
+
+        order
+    }
+
+    pub fn find_cycle(&self) -> Option<Cycle> {
+        const UNSEEN: u8 = 0;
+        const ACTIVE: u8 = 1;
+        const DONE: u8 = 2;
+
+        let mut state = vec![UNSEEN; self.len()];
+        let mut path = Vec::new();
+
+        for root in 0..self.len() {
+            if state[root] != UNSEEN {
+                continue;
+            }
+
+            state[root] = ACTIVE;
+            path.push((root, 0));
+
+            while let Some((node, i)) = path.pop() {
+                match self.edges[node].get(i) {
+                    Some((next, _)) => {
+                        path.push((node, i + 1));
+
+                        match state[*next] {
+                            UNSEEN => {
+                                state[*next] = ACTIVE;
+                                path.push((*next, 0));
+                            }
+                            ACTIVE => {
+                                let start = path.iter().position(|(n, _)| n == next).unwrap();
+                                return Some(Cycle(
+                                    path[start..].iter().map(|(n, _)| *n).collect(),
+                                ));
+                            }
+                            _ => {}
+                        }
+                    }
+                    None => state[node] = DONE,
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Orders the nodes so that every edge goes from an earlier node to a later one.
+    pub fn topological_sort(&self) -> Result<Vec<usize>, Cycle> {
+        let mut in_degree: Vec<usize> = self.reverse_edges.iter().map(|e| e.len()).collect();
+        let mut order: Vec<usize> = (0..self.len()).filter(|n| in_degree[*n] == 0).collect();
+
+        let mut i = 0;
+        while i < order.len() {
+            for (next, _) in self.edges[order[i]].iter() {
+                in_degree[*next] -= 1;
+                if in_degree[*next] == 0 {
+                    order.push(*next);
+                }
+            }
+
+            i += 1;
+        }
+
+        if order.len() < self.len() {
+            Err(self.find_cycle().unwrap())
+        } else {
+            Ok(order)
+        }
+    }
+
+    /// Computes `f(node, children)` bottom-up for `start` and everything below it, where
+    /// `children` holds the result and edge weight of each outgoing edge. Every node is
+    /// only computed once, no matter how many paths lead to it.
+    pub fn aggregate<T, F>(&self, start: usize, f: F) -> Result<T, Cycle>
+    where
+        T: Clone,
+        F: Fn(usize, &[(T, W)]) -> T,
+    {
+        let mut results: Vec<Option<T>> = vec![None; self.len()];
+        let mut active = vec![false; self.len()];
+        let mut path = vec![(start, 0)];
+        let mut children = Vec::with_capacity(8);
+        active[start] = true;
+
+        while let Some((node, i)) = path.pop() {
+            if let Some((next, _)) = self.edges[node].get(i) {
+                path.push((node, i + 1));
+
+                if results[*next].is_none() {
+                    if active[*next] {
+                        let start = path.iter().position(|(n, _)| n == next).unwrap();
+                        return Err(Cycle(path[start..].iter().map(|(n, _)| *n).collect()));
+                    }
+
+                    active[*next] = true;
+                    path.push((*next, 0));
+                }
+
+                continue;
+            }
+
+            children.clear();
+            for (next, weight) in self.edges[node].iter() {
+                children.push((results[*next].clone().unwrap(), *weight));
+            }
+
+            results[node] = Some(f(node, &children));
+            active[node] = false;
+        }
+
+        Ok(results[start].take().unwrap())
+    }
+}
+
+impl<W: Copy> Default for Graph<W> {
+    fn default() -> Self {
+        Graph::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diamond() -> Graph<u32> {
+        let mut graph = Graph::new();
+        let a = graph.node("a");
+        let b = graph.node("b");
+        let c = graph.node("c");
+        let d = graph.node("d");
+        graph.add_edge(a, b, 2);
+        graph.add_edge(a, c, 3);
+        graph.add_edge(b, d, 4);
+        graph.add_edge(c, d, 5);
+
+        graph
+    }
+
+    #[test]
+    fn test_traversal() {
+        let graph = diamond();
+
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.bfs(0, Direction::Forward), vec![0, 1, 2, 3]);
+        assert_eq!(graph.dfs(0, Direction::Forward), vec![0, 1, 3, 2]);
+        assert_eq!(graph.bfs(3, Direction::Backward), vec![3, 1, 2, 0]);
+        assert_eq!(graph.find("c"), Some(2));
+        assert_eq!(graph.name(3), "d");
+    }
+
+    #[test]
+    fn test_topological_sort() {
+        let mut graph = diamond();
+        assert_eq!(graph.topological_sort(), Ok(vec![0, 1, 2, 3]));
+        assert_eq!(graph.find_cycle(), None);
+
+        let e = graph.node("e");
+        graph.add_edge(3, e, 1);
+        graph.add_edge(e, 1, 1);
+        assert_eq!(graph.topological_sort(), Err(Cycle(vec![1, 3, 4])));
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut graph = diamond();
+        let count = |_, children: &[(u32, u32)]| -> u32 {
+            1 + children.iter().map(|(c, w)| c * w).sum::<u32>()
+        };
+
+        assert_eq!(graph.aggregate(0, count), Ok(1 + 2 * 5 + 3 * 6));
+
+        graph.add_edge(3, 0, 1);
+        assert_eq!(graph.aggregate(1, count), Err(Cycle(vec![1, 3, 0])));
+    }
+}
@@ -0,0 +1,111 @@
+use rustc_hash::FxHashMap;
+
+/// Hands out dense ids for names, starting from 0 in the order they are first seen.
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    names: Vec<String>,
+    ids: FxHashMap<String, u32>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Interner {
+        Interner {
+            names: Vec::with_capacity(capacity),
+            ids: FxHashMap::with_capacity_and_hasher(capacity, Default::default()),
+        }
+    }
+
+    /// Interns every name, with the ids following the sorted order of the names.
+    pub fn sorted<'a, I: IntoIterator<Item = &'a str>>(names: I) -> Interner {
+        let mut interner = Interner::new();
+        for name in names {
+            interner.intern(name);
+        }
+        interner.sort();
+
+        interner
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+
+        let id = self.names.len() as u32;
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), id);
+
+        id
+    }
+
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).cloned()
+    }
+
+    pub fn name(&self, id: u32) -> &str {
+        &self.names[id as usize]
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(|s| s.as_str())
+    }
+
+    /// Renumbers the ids to follow the sorted order of the names. The returned list maps
+    /// each old id to its new one.
+    pub fn sort(&mut self) -> Vec<u32> {
+        let mut order: Vec<u32> = (0..self.names.len() as u32).collect();
+        order.sort_by(|a, b| self.names[*a as usize].cmp(&self.names[*b as usize]));
+
+        let mut remap = vec![0; order.len()];
+        for (new_id, old_id) in order.iter().enumerate() {
+            remap[*old_id as usize] = new_id as u32;
+        }
+
+        self.names.sort();
+        for (id, name) in self.names.iter().enumerate() {
+            *self.ids.get_mut(name).unwrap() = id as u32;
+        }
+
+        remap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        assert_eq!(interner.intern("soy"), 0);
+        assert_eq!(interner.intern("dairy"), 1);
+        assert_eq!(interner.intern("soy"), 0);
+        assert_eq!(interner.get("fish"), None);
+        assert_eq!(interner.intern("fish"), 2);
+        assert_eq!(interner.name(1), "dairy");
+
+        assert_eq!(interner.sort(), vec![2, 0, 1]);
+        assert_eq!(interner.get("soy"), Some(2));
+        assert_eq!(interner.name(0), "dairy");
+        assert_eq!(
+            interner.names().collect::<Vec<_>>(),
+            vec!["dairy", "fish", "soy"]
+        );
+
+        let sorted = Interner::sorted(vec!["b", "c", "a", "b"]);
+        assert_eq!(sorted.len(), 3);
+        assert_eq!(sorted.get("c"), Some(2));
+    }
+}
@@ -0,0 +1,275 @@
+use num::PrimInt;
+use rustc_hash::FxHashMap;
+use std::hash::Hash;
+
+/// Beyond this many half-size combinations, meet-in-the-middle uses too much memory.
+const MAX_HALF_COMBINATIONS: usize = 1 << 20;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Strategy {
+    /// Picks one of the others based on `k` and the number of values.
+    Auto,
+    /// Fixes all but two values, and finds the last one of those with a hash map.
+    Hashing,
+    /// Sorts the values, fixes all but two, and closes in on the last two from both ends.
+    TwoPointer,
+    /// Puts the sums of every half-size combination in a hash map, and looks up the other
+    /// half's complement in it.
+    MeetInTheMiddle,
+}
+
+/// Finds `k` values at distinct indices that add up to `target`, returning the indices in
+/// ascending order. Combinations where adding up the values overflows `T` along the way
+/// aren't found.
+pub fn k_sum<T: PrimInt + Hash>(values: &[T], k: usize, target: T) -> Option<Vec<usize>> {
+    k_sum_with(values, k, target, Strategy::Auto)
+}
+
+pub fn k_sum_with<T: PrimInt + Hash>(
+    values: &[T],
+    k: usize,
+    target: T,
+    strategy: Strategy,
+) -> Option<Vec<usize>> {
+    if k > values.len() {
+        return None;
+    }
+
+    let mut result = match strategy {
+        Strategy::Auto if k <= 2 => hashing(values, k, target),
+        Strategy::Auto if k >= 4 && combinations_fit(values.len(), k / 2) => {
+            meet_in_the_middle(values, k, target)
+        }
+        Strategy::Auto => two_pointer(values, k, target),
+        Strategy::Hashing => hashing(values, k, target),
+        Strategy::TwoPointer => two_pointer(values, k, target),
+        Strategy::MeetInTheMiddle => meet_in_the_middle(values, k, target),
+    }?;
+
+    result.sort_unstable();
+    Some(result)
+}
+
+fn combinations_fit(n: usize, k: usize) -> bool {
+    let mut count = 1usize;
+    for i in 0..k {
+        count = count * (n - i) / (i + 1);
+        if count > MAX_HALF_COMBINATIONS {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Calls `f` with every increasing list of `k` indices below `n` until it returns true.
+fn for_each_combination(n: usize, k: usize, mut f: impl FnMut(&[usize]) -> bool) -> bool {
+    let mut indices: Vec<usize> = (0..k).collect();
+    if k > n {
+        return false;
+    }
+
+    loop {
+        if f(&indices) {
+            return true;
+        }
+
+        // Bump the last index that still has room, and reset the ones after it.
+        let mut i = k;
+        loop {
+            if i == 0 {
+                return false;
+            }
+            i -= 1;
+            if indices[i] < n - k + i {
+                break;
+            }
+        }
+
+        indices[i] += 1;
+        for j in i + 1..k {
+            indices[j] = indices[j - 1] + 1;
+        }
+    }
+}
+
+fn sum<T: PrimInt>(values: &[T], indices: &[usize]) -> Option<T> {
+    indices
+        .iter()
+        .try_fold(T::zero(), |acc, i| acc.checked_add(&values[*i]))
+}
+
+fn hashing<T: PrimInt + Hash>(values: &[T], k: usize, target: T) -> Option<Vec<usize>> {
+    if k < 2 {
+        let mut result = None;
+        for_each_combination(values.len(), k, |indices| {
+            if sum(values, indices) == Some(target) {
+                result = Some(indices.to_vec());
+            }
+            result.is_some()
+        });
+
+        return result;
+    }
+
+    let mut seen: FxHashMap<T, usize> = FxHashMap::default();
+    let mut result = None;
+
+    // The first k - 2 indices are fixed, and the last two come after them.
+    for_each_combination(values.len(), k - 2, |fixed| {
+        let rest = match sum(values, fixed).and_then(|s| target.checked_sub(&s)) {
+            Some(rest) => rest,
+            None => return false,
+        };
+        let start = fixed.last().map(|i| i + 1).unwrap_or(0);
+
+        seen.clear();
+        for (j, value) in values.iter().enumerate().skip(start) {
+            if let Some(i) = rest.checked_sub(value).and_then(|v| seen.get(&v)) {
+                let mut indices = fixed.to_vec();
+                indices.push(*i);
+                indices.push(j);
+                result = Some(indices);
+                return true;
+            }
+
+            seen.entry(*value).or_insert(j);
+        }
+
+        false
+    });
+
+    result
+}
+
+fn two_pointer<T: PrimInt + Hash>(values: &[T], k: usize, target: T) -> Option<Vec<usize>> {
+    if k < 2 {
+        return hashing(values, k, target);
+    }
+
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by_key(|i| values[*i]);
+    let sorted: Vec<T> = order.iter().map(|i| values[*i]).collect();
+    let mut result = None;
+
+    for_each_combination(sorted.len(), k - 2, |fixed| {
+        let rest = match sum(&sorted, fixed).and_then(|s| target.checked_sub(&s)) {
+            Some(rest) => rest,
+            None => return false,
+        };
+
+        let mut lo = fixed.last().map(|i| i + 1).unwrap_or(0);
+        let mut hi = sorted.len() - 1;
+        while lo < hi {
+            match sorted[lo].checked_add(&sorted[hi]) {
+                Some(s) if s == rest => {
+                    let mut indices: Vec<usize> = fixed.iter().map(|i| order[*i]).collect();
+                    indices.push(order[lo]);
+                    indices.push(order[hi]);
+                    result = Some(indices);
+                    return true;
+                }
+                Some(s) if s < rest => lo += 1,
+                // Overflowing means the sum was too large if the high value is positive.
+                None if sorted[hi] < T::zero() => lo += 1,
+                _ => hi -= 1,
+            }
+        }
+
+        false
+    });
+
+    result
+}
+
+fn meet_in_the_middle<T: PrimInt + Hash>(values: &[T], k: usize, target: T) -> Option<Vec<usize>> {
+    let half = k / 2;
+    let mut sums: FxHashMap<T, Vec<Vec<usize>>> = FxHashMap::default();
+
+    for_each_combination(values.len(), half, |indices| {
+        if let Some(s) = sum(values, indices) {
+            sums.entry(s).or_default().push(indices.to_vec());
+        }
+        false
+    });
+
+    let mut result = None;
+    for_each_combination(values.len(), k - half, |indices| {
+        let rest = match sum(values, indices).and_then(|s| target.checked_sub(&s)) {
+            Some(rest) => rest,
+            None => return false,
+        };
+
+        if let Some(candidates) = sums.get(&rest) {
+            if let Some(other) = candidates
+                .iter()
+                .find(|other| other.iter().all(|i| !indices.contains(i)))
+            {
+                let mut combined = other.clone();
+                combined.extend_from_slice(indices);
+                result = Some(combined);
+                return true;
+            }
+        }
+
+        false
+    });
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRATEGIES: &[Strategy] = &[
+        Strategy::Auto,
+        Strategy::Hashing,
+        Strategy::TwoPointer,
+        Strategy::MeetInTheMiddle,
+    ];
+
+    #[test]
+    fn test_k_sum() {
+        let expenses: &[u32] = &[1721, 979, 366, 299, 675, 1456];
+
+        for strategy in STRATEGIES.iter().cloned() {
+            assert_eq!(
+                k_sum_with(expenses, 2, 2020, strategy),
+                Some(vec![0, 3]),
+                "{:?}",
+                strategy
+            );
+            assert_eq!(
+                k_sum_with(expenses, 3, 2020, strategy),
+                Some(vec![1, 2, 4]),
+                "{:?}",
+                strategy
+            );
+            assert_eq!(k_sum_with(expenses, 2, 2021, strategy), None);
+            assert_eq!(k_sum_with(expenses, 7, 0, strategy), None);
+        }
+
+        assert_eq!(k_sum(expenses, 1, 366), Some(vec![2]));
+        assert_eq!(k_sum(expenses, 0, 0), Some(vec![]));
+    }
+
+    #[test]
+    fn test_k_sum_large() {
+        let values: Vec<i64> = (0..60)
+            .map(|i| (i * 7919 % 1013) * 1_000_000_007 - 500)
+            .collect();
+        let target = values[3] + values[17] + values[42] + values[59];
+
+        for strategy in STRATEGIES.iter().cloned() {
+            let indices = k_sum_with(&values, 4, target, strategy).unwrap();
+            assert_eq!(indices.len(), 4);
+            assert!(indices.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(indices.iter().map(|i| values[*i]).sum::<i64>(), target);
+        }
+
+        let extremes = [i64::MAX, i64::MAX, -1, i64::MIN, 1];
+        assert_eq!(k_sum(&extremes, 2, -1), Some(vec![0, 3]));
+        assert_eq!(k_sum(&extremes, 2, i64::MAX - 2), None);
+    }
+}
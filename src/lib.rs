@@ -1,5 +1,23 @@
 extern crate num;
 
+pub mod answers;
 pub mod aoc;
+pub mod bitset;
+pub mod cfg;
+pub mod chain;
+pub mod coprocessor;
+#[cfg(test)]
+mod difftest;
+pub mod expr;
+pub mod graph;
 pub mod grid;
+pub mod intern;
+pub mod ksum;
+pub mod math;
 pub mod parsers;
+pub mod policy;
+pub mod random;
+pub mod schedule;
+pub mod search;
+pub mod solve;
+pub mod vm;
@@ -0,0 +1,340 @@
+use num::integer::Roots;
+use num::{Integer, PrimInt, Signed};
+use rustc_hash::FxHashMap;
+use std::hash::Hash;
+
+/// `(a + b) % m` for `a` and `b` in `0..m`, without overflowing.
+pub fn add_mod<T: PrimInt + Integer>(a: T, b: T, m: T) -> T {
+    if a >= m - b {
+        a - (m - b)
+    } else {
+        a + b
+    }
+}
+
+/// `(a * b) % m` for `a` and `b` in `0..m`, falling back to double-and-add when the product
+/// doesn't fit in `T`.
+pub fn mul_mod<T: PrimInt + Integer>(a: T, b: T, m: T) -> T {
+    if let Some(product) = a.checked_mul(&b) {
+        return product % m;
+    }
+
+    let (mut a, mut b) = (a, b);
+    let mut result = T::zero();
+    while b > T::zero() {
+        if b & T::one() == T::one() {
+            result = add_mod(result, a, m);
+        }
+        a = add_mod(a, a, m);
+        b = b >> 1;
+    }
+
+    result
+}
+
+/// `base.pow(exp) % m`, for a non-negative exponent.
+pub fn mod_pow<T: PrimInt + Integer>(base: T, exp: T, m: T) -> T {
+    let mut base = base.mod_floor(&m);
+    let mut exp = exp;
+    let mut result = T::one() % m;
+
+    while exp > T::zero() {
+        if exp & T::one() == T::one() {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp = exp >> 1;
+    }
+
+    result
+}
+
+/// Returns `(g, x, y)` where `g` is the greatest common divisor of `a` and `b`, and
+/// `a * x + b * y == g`.
+pub fn egcd<T: PrimInt + Integer + Signed>(a: T, b: T) -> (T, T, T) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (T::one(), T::zero());
+    let (mut old_y, mut y) = (T::zero(), T::one());
+
+    while r != T::zero() {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+
+    if old_r < T::zero() {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+/// The `x` in `0..m` where `a * x % m == 1`, if `a` and `m` are coprime.
+pub fn mod_inv<T: PrimInt + Integer + Signed>(a: T, m: T) -> Option<T> {
+    let (g, x, _) = egcd(a.mod_floor(&m), m);
+    if g == T::one() {
+        Some(x.mod_floor(&m))
+    } else {
+        None
+    }
+}
+
+/// Solves the congruences `x % m == a` for every `(a, m)`, returning `x` and the modulus it
+/// is unique under. The moduli don't have to be coprime. Returns `None` if the congruences
+/// contradict each other or the combined modulus doesn't fit in `T`.
+pub fn crt<T: PrimInt + Integer + Signed>(congruences: &[(T, T)]) -> Option<(T, T)> {
+    let mut x = T::zero();
+    let mut m = T::one();
+
+    for (a, n) in congruences.iter().cloned() {
+        let a = a.mod_floor(&n);
+        let (g, p, _) = egcd(m, n);
+        let diff = (a - x).mod_floor(&n);
+        if diff % g != T::zero() {
+            return None;
+        }
+
+        // x + m * k is a solution for every k = diff / g * p (mod n / g).
+        let step = n / g;
+        let k = mul_mod((diff / g).mod_floor(&step), p.mod_floor(&step), step);
+        let lcm = m.checked_mul(&step)?;
+        x = add_mod(x, mul_mod(m, k, lcm), lcm);
+        m = lcm;
+    }
+
+    Some((x, m))
+}
+
+/// Miller-Rabin with the first thirteen primes as witnesses, which never fails below 3.3e24.
+pub fn is_prime<T: PrimInt + Integer>(n: T) -> bool {
+    const WITNESSES: [u8; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+    let one = T::one();
+    if n <= one {
+        return false;
+    }
+
+    let mut d = n - one;
+    let mut shift = 0;
+    while d.is_even() {
+        d = d >> 1;
+        shift += 1;
+    }
+
+    'witness: for w in WITNESSES.iter() {
+        let w = match T::from(*w) {
+            Some(w) if w < n => w,
+            _ => return WITNESSES.iter().any(|p| T::from(*p) == Some(n)),
+        };
+
+        let mut x = mod_pow(w, d, n);
+        if x == one || x == n - one {
+            continue;
+        }
+        for _ in 1..shift {
+            x = mul_mod(x, x, n);
+            if x == n - one {
+                continue 'witness;
+            }
+        }
+
+        return false;
+    }
+
+    true
+}
+
+/// The prime factors of `n` with their exponents, smallest first.
+pub fn factorize<T: PrimInt + Integer>(n: T) -> Vec<(T, u32)> {
+    let mut factors = Vec::new();
+    let mut n = n;
+    let mut p = T::one() + T::one();
+
+    // Trial division, stopping early once what's left is prime.
+    let mut done = is_prime(n);
+    while !done && p <= n / p {
+        let mut exponent = 0;
+        while n % p == T::zero() {
+            n = n / p;
+            exponent += 1;
+        }
+        if exponent > 0 {
+            factors.push((p, exponent));
+            done = is_prime(n);
+        }
+
+        p = p + T::one();
+    }
+    if n > T::one() {
+        factors.push((n, 1));
+    }
+
+    factors
+}
+
+/// The smallest `x` in `0..order` where `base.pow(x) % m == target`, using baby-step
+/// giant-step. `base` must be coprime with `m`.
+pub fn bsgs<T>(base: T, target: T, m: T, order: T) -> Option<T>
+where
+    T: PrimInt + Integer + Roots + Hash,
+{
+    let target = target.mod_floor(&m);
+    if target == T::one() % m {
+        return Some(T::zero());
+    }
+
+    let steps = order.sqrt() + T::one();
+
+    // target * base^j for every j, keeping the highest j for each value.
+    let mut baby_steps = FxHashMap::default();
+    let mut value = target;
+    let mut j = T::zero();
+    while j < steps {
+        baby_steps.insert(value, j);
+        value = mul_mod(value, base, m);
+        j = j + T::one();
+    }
+
+    // base^(i * steps) == target * base^j means base^(i * steps - j) == target.
+    let giant_step = mod_pow(base, steps, m);
+    let mut value = T::one() % m;
+    let mut i = T::zero();
+    while i < steps {
+        i = i + T::one();
+        value = mul_mod(value, giant_step, m);
+
+        if let Some(j) = baby_steps.get(&value) {
+            let x = i * steps - *j;
+            return if x < order { Some(x) } else { None };
+        }
+    }
+
+    None
+}
+
+/// The smallest `x` where `base.pow(x) % m == target`, for a `base` coprime with `m`. The
+/// problem is split up by the prime factors of the group order with Pohlig-Hellman, so it's
+/// fast whenever those are small.
+pub fn discrete_log<T>(base: T, target: T, m: T) -> Option<T>
+where
+    T: PrimInt + Integer + Roots + Hash,
+{
+    let base = base.mod_floor(&m);
+    let target = target.mod_floor(&m);
+    if base.gcd(&m) != T::one() {
+        return if target == T::one() % m {
+            Some(T::zero())
+        } else {
+            None
+        };
+    }
+
+    // Euler's totient, which the order of every element divides.
+    let order = factorize(m)
+        .into_iter()
+        .fold(T::one(), |acc, (p, e)| acc * (p - T::one()) * p.pow(e - 1));
+
+    let mut x = T::zero();
+    let mut combined = T::one();
+    for (q, e) in factorize(order) {
+        // Move to the subgroup of order q^e, then find how far that's actually reached.
+        let cofactor = order / q.pow(e);
+        let sub_base = mod_pow(base, cofactor, m);
+        let sub_target = mod_pow(target, cofactor, m);
+
+        let mut sub_order = T::one();
+        while mod_pow(sub_base, sub_order, m) != T::one() % m {
+            sub_order = sub_order * q;
+        }
+
+        if sub_order == T::one() {
+            continue;
+        }
+
+        let sub_x = bsgs(sub_base, sub_target, m, sub_order)?;
+
+        // Combine x (mod combined) with sub_x (mod sub_order). The orders are powers of
+        // distinct primes, so the inverse comes from Euler's theorem.
+        let totient = sub_order / q * (q - T::one());
+        let inv = mod_pow(combined % sub_order, totient - T::one(), sub_order);
+        let diff = add_mod(sub_x, sub_order - x % sub_order, sub_order) % sub_order;
+        let k = mul_mod(diff, inv, sub_order);
+
+        let next = combined * sub_order;
+        x = add_mod(x, mul_mod(combined, k, next), next);
+        combined = next;
+    }
+
+    if mod_pow(base, x, m) == target {
+        Some(x)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mod_arithmetic() {
+        assert_eq!(mod_pow(7u64, 8, 20201227), 5764801);
+        assert_eq!(mod_pow(4u8, 13, 251), 249);
+        assert_eq!(mul_mod(u64::MAX - 1, u64::MAX - 2, u64::MAX), 2);
+        assert_eq!(mod_pow(3i128, 1 << 100, 1_000_000_007), 870513414);
+
+        assert_eq!(egcd(240, 46), (2, -9, 47));
+        assert_eq!(mod_inv(3, 11), Some(4));
+        assert_eq!(mod_inv(-3i128, 11), Some(7));
+        assert_eq!(mod_inv(6, 9), None);
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        assert_eq!(crt(&[(2i8, 4), (4, 6)]), Some((10, 12)));
+        assert_eq!(crt(&[(1, 4), (2, 6)]), None);
+        assert_eq!(crt(&[(1i16, 251), (2, 241)]), None);
+        assert_eq!(crt::<i64>(&[]), Some((0, 1)));
+
+        let big: i128 = 1_000_000_000_000_000_003;
+        let (x, m) = crt(&[(5, big), (7, 1_000_000_007)]).unwrap();
+        assert_eq!(m, big * 1_000_000_007);
+        assert_eq!((x % big, x % 1_000_000_007), (5, 7));
+    }
+
+    #[test]
+    fn test_discrete_log() {
+        assert_eq!(
+            factorize(20201226u64),
+            vec![(2, 1), (3, 1), (29, 1), (116099, 1)]
+        );
+        assert_eq!(factorize(360u32), vec![(2, 3), (3, 2), (5, 1)]);
+        assert!(is_prime(2u8) && is_prime(251u8) && !is_prime(1u8) && !is_prime(221u8));
+        assert!(is_prime(18446744073709551557u64) && !is_prime(3215031751u64));
+
+        // The smallest composite that passes every witness up to 37, the prime after it, and
+        // the largest prime below the bound.
+        assert!(!is_prime(318665857834031151167461u128));
+        assert!(is_prime(318665857834031151167483u128));
+        assert!(is_prime(3317044064679887385961813u128));
+
+        assert_eq!(bsgs(7u64, 5764801, 20201227, 20201226), Some(8));
+        assert_eq!(discrete_log(7u64, 17807724, 20201227), Some(11));
+        assert_eq!(discrete_log(7u64, 1, 20201227), Some(0));
+
+        // 2 has order 11 modulo 23, so 5 isn't reachable and 13 is.
+        assert_eq!(discrete_log(2i32, 5, 23), None);
+        assert_eq!(discrete_log(2i32, 13, 23), Some(7));
+        assert_eq!(
+            discrete_log(3u64, mod_pow(3, 1000, 1 << 20), 1 << 20),
+            Some(1000)
+        );
+        assert_eq!(discrete_log(4u8, 3, 6), None);
+
+        let p: u64 = (1 << 61) - 1;
+        let x = 123_456_789_012_345;
+        assert_eq!(discrete_log(3, mod_pow(3, x, p), p), Some(x));
+    }
+}
@@ -1,3 +1,5 @@
+use num::Signed;
+
 const ZERO_U64: u64 = '0' as u64;
 const ZERO_USIZE: usize = '0' as usize;
 
@@ -44,3 +46,30 @@ pub fn parse_usize(s: &str) -> usize {
 
     res
 }
+
+pub fn parse_i32(s: &str) -> i32 {
+    parse_signed(s)
+}
+
+pub fn parse_i64(s: &str) -> i64 {
+    parse_signed(s)
+}
+
+/// Reads the digits of `s`, skipping anything else, with the last sign seen applying to the
+/// whole number.
+fn parse_signed<T: Signed + Copy + From<u8>>(s: &str) -> T {
+    let ten = T::from(10);
+    let mut res = T::zero();
+    let mut sign = T::one();
+
+    for c in s.bytes() {
+        match c {
+            b'+' => sign = T::one(),
+            b'-' => sign = -T::one(),
+            b'0'..=b'9' => res = res * ten + T::from(c - b'0'),
+            _ => {}
+        }
+    }
+
+    sign * res
+}
@@ -0,0 +1,356 @@
+use std::fmt;
+
+/// A rule for passwords. The `Display` output is the rule in the spec format `AllOf::parse`
+/// reads, which can't read back rules built with a space, `;` or newline as their character.
+pub trait Policy: fmt::Display {
+    fn accepts(&self, password: &str) -> bool;
+
+    /// Adds the rules that reject the password to `failed`.
+    fn failed_rules<'a>(&'a self, password: &str, failed: &mut Vec<&'a dyn Policy>);
+}
+
+/// Passwords that some rule of the policy rejects, along with those rules.
+pub fn report<'a, P: Policy>(
+    policy: &P,
+    passwords: impl Iterator<Item = &'a str>,
+) -> Vec<(&'a str, Vec<String>)> {
+    let mut res = Vec::new();
+    let mut failed = Vec::new();
+
+    for password in passwords {
+        failed.clear();
+        policy.failed_rules(password, &mut failed);
+
+        if !failed.is_empty() {
+            res.push((password, failed.iter().map(|p| p.to_string()).collect()));
+        }
+    }
+
+    res
+}
+
+macro_rules! impl_failed_rules {
+    () => {
+        fn failed_rules<'a>(&'a self, password: &str, failed: &mut Vec<&'a dyn Policy>) {
+            if !self.accepts(password) {
+                failed.push(self);
+            }
+        }
+    };
+}
+
+/// The character has to appear between `min` and `max` times.
+pub struct CountRange {
+    pub ch: u8,
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Policy for CountRange {
+    fn accepts(&self, password: &str) -> bool {
+        let count = password.bytes().filter(|b| *b == self.ch).count();
+
+        count >= self.min && count <= self.max
+    }
+
+    impl_failed_rules!();
+}
+
+impl fmt::Display for CountRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "count {} {}-{}", self.ch as char, self.min, self.max)
+    }
+}
+
+/// The character has to be at exactly one of the two 1-based positions.
+pub struct ExactlyOneAt {
+    pub ch: u8,
+    pub first: usize,
+    pub second: usize,
+}
+
+impl Policy for ExactlyOneAt {
+    fn accepts(&self, password: &str) -> bool {
+        let at = |pos: usize| password.as_bytes().get(pos.wrapping_sub(1)) == Some(&self.ch);
+
+        at(self.first) != at(self.second)
+    }
+
+    impl_failed_rules!();
+}
+
+impl fmt::Display for ExactlyOneAt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "one-of {} {} {}",
+            self.ch as char, self.first, self.second
+        )
+    }
+}
+
+/// A set of characters written like in a regex, e.g. `[a-z0-9_]` or `[^aeiou]`.
+pub struct CharClass {
+    spec: String,
+    ranges: Vec<(u8, u8)>,
+    negated: bool,
+}
+
+impl CharClass {
+    pub fn contains(&self, b: u8) -> bool {
+        self.ranges.iter().any(|(lo, hi)| b >= *lo && b <= *hi) != self.negated
+    }
+
+    pub fn parse(s: &str) -> Result<CharClass, String> {
+        let inner = s
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(|| format!("bad character class: {}", s))?;
+        let (negated, inner) = match inner.strip_prefix('^') {
+            Some(inner) => (true, inner),
+            None => (false, inner),
+        };
+
+        let bytes = inner.as_bytes();
+        let mut ranges = Vec::with_capacity(4);
+        let mut i = 0;
+        while i < bytes.len() {
+            if i + 2 < bytes.len() && bytes[i + 1] == b'-' {
+                ranges.push((bytes[i], bytes[i + 2]));
+                i += 3;
+            } else {
+                ranges.push((bytes[i], bytes[i]));
+                i += 1;
+            }
+        }
+
+        Ok(CharClass {
+            spec: s.to_owned(),
+            ranges,
+            negated,
+        })
+    }
+}
+
+/// Between `min` and `max` characters have to be in the class.
+pub struct ClassCount {
+    pub class: CharClass,
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Policy for ClassCount {
+    fn accepts(&self, password: &str) -> bool {
+        let count = password.bytes().filter(|b| self.class.contains(*b)).count();
+
+        count >= self.min && count <= self.max
+    }
+
+    impl_failed_rules!();
+}
+
+impl fmt::Display for ClassCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "class {} {}-{}", self.class.spec, self.min, self.max)
+    }
+}
+
+/// At least this many different characters.
+pub struct MinDistinct(pub usize);
+
+impl Policy for MinDistinct {
+    fn accepts(&self, password: &str) -> bool {
+        let mut seen = [false; 256];
+        for b in password.bytes() {
+            seen[b as usize] = true;
+        }
+
+        seen.iter().filter(|s| **s).count() >= self.0
+    }
+
+    impl_failed_rules!();
+}
+
+impl fmt::Display for MinDistinct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "distinct {}", self.0)
+    }
+}
+
+/// The substring can't appear anywhere in the password.
+pub struct Forbidden(String);
+
+impl Forbidden {
+    /// Fails for substrings that can't be written in a spec: empty ones, ones that start or
+    /// end with whitespace, and ones with a `;` or a newline.
+    pub fn new(substring: &str) -> Result<Forbidden, String> {
+        let trimmed = substring.trim();
+        if trimmed.is_empty() || trimmed != substring || substring.contains(['\n', ';']) {
+            return Err(format!("can't forbid {:?}", substring));
+        }
+
+        Ok(Forbidden(substring.to_owned()))
+    }
+}
+
+impl Policy for Forbidden {
+    fn accepts(&self, password: &str) -> bool {
+        !password.contains(self.0.as_str())
+    }
+
+    impl_failed_rules!();
+}
+
+impl fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "forbid {}", self.0)
+    }
+}
+
+/// Every rule has to pass.
+pub struct AllOf(pub Vec<Box<dyn Policy>>);
+
+impl AllOf {
+    /// Reads rules separated by newlines or `;`, each being one of:
+    ///
+    /// - `count <char> <min>-<max>`
+    /// - `one-of <char> <pos> <pos>`
+    /// - `class <[chars]> <min>-<max>`
+    /// - `distinct <n>`
+    /// - `forbid <substring>`
+    ///
+    /// The spec is split into rules before they are read, so no rule can contain a `;`.
+    /// That includes the substring of `forbid`, even though it takes the rest of the rule,
+    /// spaces and all.
+    pub fn parse(spec: &str) -> Result<AllOf, String> {
+        let mut rules: Vec<Box<dyn Policy>> = Vec::new();
+
+        for rule in spec.split(['\n', ';']) {
+            let rule = rule.trim();
+            if rule.is_empty() {
+                continue;
+            }
+
+            let (kind, args) = rule.split_at(rule.find(' ').unwrap_or(rule.len()));
+            let args = args.trim_start();
+            let words: Vec<&str> = args.split(' ').collect();
+
+            rules.push(match (kind, words.as_slice()) {
+                ("count", [ch, range]) => {
+                    let (min, max) = parse_range(range)?;
+                    Box::new(CountRange {
+                        ch: parse_char(ch)?,
+                        min,
+                        max,
+                    })
+                }
+                ("one-of", [ch, first, second]) => Box::new(ExactlyOneAt {
+                    ch: parse_char(ch)?,
+                    first: parse_number(first)?,
+                    second: parse_number(second)?,
+                }),
+                ("class", [class, range]) => {
+                    let (min, max) = parse_range(range)?;
+                    Box::new(ClassCount {
+                        class: CharClass::parse(class)?,
+                        min,
+                        max,
+                    })
+                }
+                ("distinct", [n]) => Box::new(MinDistinct(parse_number(n)?)),
+                ("forbid", _) if !args.is_empty() => Box::new(Forbidden::new(args)?),
+                _ => return Err(format!("bad rule: {}", rule)),
+            });
+        }
+
+        Ok(AllOf(rules))
+    }
+}
+
+impl Policy for AllOf {
+    fn accepts(&self, password: &str) -> bool {
+        self.0.iter().all(|rule| rule.accepts(password))
+    }
+
+    fn failed_rules<'a>(&'a self, password: &str, failed: &mut Vec<&'a dyn Policy>) {
+        for rule in self.0.iter() {
+            rule.failed_rules(password, failed);
+        }
+    }
+}
+
+impl fmt::Display for AllOf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, rule) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", rule)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_char(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [b] => Ok(*b),
+        _ => Err(format!("not a single character: {}", s)),
+    }
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("not a number: {}", s))
+}
+
+fn parse_range(s: &str) -> Result<(usize, usize), String> {
+    let dash = s.find('-').ok_or_else(|| format!("not a range: {}", s))?;
+
+    Ok((parse_number(&s[..dash])?, parse_number(&s[dash + 1..])?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_spec() {
+        let spec = "class [a-c] 1-3; distinct 4\nforbid cc;one-of e 5 1";
+        let policy = AllOf::parse(spec).unwrap();
+        assert_eq!(
+            policy.to_string(),
+            "class [a-c] 1-3; distinct 4; forbid cc; one-of e 5 1"
+        );
+
+        let passwords = vec!["abcde", "cdefg", "ccccccccc"];
+        assert_eq!(
+            report(&policy, passwords.into_iter()),
+            vec![
+                ("cdefg", vec!["one-of e 5 1".to_owned()]),
+                (
+                    "ccccccccc",
+                    vec![
+                        "class [a-c] 1-3".to_owned(),
+                        "distinct 4".to_owned(),
+                        "forbid cc".to_owned(),
+                        "one-of e 5 1".to_owned(),
+                    ]
+                ),
+            ]
+        );
+
+        let class = CharClass::parse("[^a-y_]").unwrap();
+        assert!(class.contains(b'z') && !class.contains(b'_') && !class.contains(b'b'));
+        assert!(AllOf::parse("count ab 1-2").is_err());
+        assert!(AllOf::parse("distinct").is_err());
+
+        // A `;` always ends a rule, so a spec can't forbid a substring with one in it.
+        let policy = AllOf::parse("forbid a  b ").unwrap();
+        assert_eq!(
+            AllOf::parse(&policy.to_string()).unwrap().to_string(),
+            "forbid a  b"
+        );
+        assert_eq!(AllOf::parse("forbid a;b").err().unwrap(), "bad rule: b");
+        assert!(Forbidden::new("a;b").is_err() && Forbidden::new(" a").is_err());
+    }
+}
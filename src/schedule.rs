@@ -0,0 +1,154 @@
+use crate::math::crt;
+use num::Integer;
+
+/// A bus that leaves every `id` minutes, wanted at `offset` minutes after a timestamp.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Bus {
+    pub id: i64,
+    pub offset: i64,
+}
+
+impl Bus {
+    pub fn new(id: i64, offset: i64) -> Bus {
+        Bus { id, offset }
+    }
+
+    /// The first time at or after `time` that the bus leaves.
+    pub fn departure_after(&self, time: i64) -> Option<i64> {
+        time.checked_add((-time).mod_floor(&self.id))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScheduleError {
+    /// A bus with an id that isn't positive.
+    InvalidId(Bus),
+    /// Two buses whose ids share a factor that makes their offsets impossible to meet at
+    /// the same time.
+    Unsatisfiable(Bus, Bus),
+    /// The pattern only repeats after longer than an `i64` can hold.
+    TooLarge,
+}
+
+/// The timestamps where every bus in a set leaves at its offset, which are the ones
+/// congruent to `start` modulo `period`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Pattern {
+    start: i64,
+    period: i64,
+}
+
+impl Pattern {
+    /// Combines the buses into a pattern. Any subset of a schedule's buses works, for a
+    /// partial pattern.
+    pub fn new<'a, I: IntoIterator<Item = &'a Bus>>(buses: I) -> Result<Pattern, ScheduleError> {
+        let buses: Vec<Bus> = buses.into_iter().cloned().collect();
+        if let Some(bus) = buses.iter().find(|b| b.id <= 0) {
+            return Err(ScheduleError::InvalidId(*bus));
+        }
+
+        let congruences: Vec<(i64, i64)> = buses.iter().map(|b| (-b.offset, b.id)).collect();
+        if let Some((start, period)) = crt(&congruences) {
+            return Ok(Pattern { start, period });
+        }
+
+        // Congruences that agree pairwise agree overall, so a failure is either a pair that
+        // conflicts or an overflow.
+        for (i, a) in buses.iter().enumerate() {
+            for b in buses[i + 1..].iter() {
+                if (a.offset - b.offset) % a.id.gcd(&b.id) != 0 {
+                    return Err(ScheduleError::Unsatisfiable(*a, *b));
+                }
+            }
+        }
+
+        Err(ScheduleError::TooLarge)
+    }
+
+    /// How often the pattern repeats.
+    pub fn period(&self) -> i64 {
+        self.period
+    }
+
+    /// The first non-negative timestamp.
+    pub fn earliest(&self) -> i64 {
+        self.start
+    }
+
+    /// The first timestamp at or after `time`, if it fits in an `i64`.
+    pub fn earliest_after(&self, time: i64) -> Option<i64> {
+        time.checked_add((self.start - time.mod_floor(&self.period)).mod_floor(&self.period))
+    }
+
+    /// Every timestamp from `time` on, as long as they fit in an `i64`. Use `take` for the
+    /// next few.
+    pub fn timestamps_from(&self, time: i64) -> impl Iterator<Item = i64> {
+        let period = self.period;
+        std::iter::successors(self.earliest_after(time), move |t| t.checked_add(period))
+    }
+}
+
+/// The bus that leaves first at or after `time`, with when it leaves.
+pub fn next_departure(buses: &[Bus], time: i64) -> Option<(Bus, i64)> {
+    buses
+        .iter()
+        .filter(|b| b.id > 0)
+        .filter_map(|b| Some((*b, b.departure_after(time)?)))
+        .min_by_key(|(_, t)| *t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern() {
+        let buses = [
+            Bus::new(7, 0),
+            Bus::new(13, 1),
+            Bus::new(59, 4),
+            Bus::new(31, 6),
+            Bus::new(19, 7),
+        ];
+
+        let pattern = Pattern::new(&buses).unwrap();
+        assert_eq!(pattern.earliest(), 1068781);
+        assert_eq!(pattern.period(), 7 * 13 * 59 * 31 * 19);
+        assert_eq!(pattern.earliest_after(1068781), Some(1068781));
+        assert_eq!(
+            pattern.timestamps_from(1068782).take(2).collect::<Vec<_>>(),
+            vec![1068781 + 3162341, 1068781 + 2 * 3162341]
+        );
+        assert_eq!(pattern.earliest_after(-3162341), Some(1068781 - 3162341));
+
+        let partial = Pattern::new(buses.iter().filter(|b| b.id < 20)).unwrap();
+        assert_eq!(partial.earliest(), 259);
+        assert_eq!(Pattern::new(&[]).unwrap().earliest(), 0);
+
+        assert_eq!(next_departure(&buses, 939), Some((Bus::new(59, 4), 944)));
+        assert_eq!(next_departure(&buses, 0).map(|(_, t)| t), Some(0));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Pattern::new(&[Bus::new(3, 0), Bus::new(4, 1), Bus::new(6, 1)]),
+            Err(ScheduleError::Unsatisfiable(Bus::new(3, 0), Bus::new(6, 1)))
+        );
+        assert_eq!(
+            Pattern::new(&[Bus::new(4, 1), Bus::new(6, 3)]).map(|p| p.earliest()),
+            Ok(3)
+        );
+        assert_eq!(
+            Pattern::new(&[Bus::new(0, 1)]),
+            Err(ScheduleError::InvalidId(Bus::new(0, 1)))
+        );
+        assert_eq!(
+            Pattern::new(&[Bus::new(4_000_000_007, 0), Bus::new(4_000_000_009, 0)]),
+            Err(ScheduleError::TooLarge)
+        );
+
+        let pattern = Pattern::new(&[Bus::new(1 << 62, 1)]).unwrap();
+        assert_eq!(pattern.timestamps_from(0).count(), 2);
+    }
+}
@@ -0,0 +1,415 @@
+use rustc_hash::FxHashSet;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::hash::{BuildHasher, Hash};
+use std::ops::Add;
+
+/// Decides which states a search has already been through.
+pub trait Visited<S> {
+    /// Marks the state as visited, returning false if it already was.
+    fn visit(&mut self, state: &S) -> bool;
+}
+
+/// Visits every state, for searches over trees or where the successors never revisit a state.
+pub struct Unvisited;
+
+impl<S> Visited<S> for Unvisited {
+    fn visit(&mut self, _: &S) -> bool {
+        true
+    }
+}
+
+impl<S: Hash + Eq + Clone, H: BuildHasher> Visited<S> for HashSet<S, H> {
+    fn visit(&mut self, state: &S) -> bool {
+        if self.contains(state) {
+            return false;
+        }
+
+        self.insert(state.clone())
+    }
+}
+
+/// Tells states apart by a key, for when only part of the state matters.
+pub struct ByKey<K, F> {
+    seen: FxHashSet<K>,
+    key: F,
+}
+
+impl<K, F> ByKey<K, F> {
+    pub fn new(key: F) -> ByKey<K, F> {
+        ByKey {
+            seen: FxHashSet::default(),
+            key,
+        }
+    }
+}
+
+impl<S, K: Hash + Eq, F: FnMut(&S) -> K> Visited<S> for ByKey<K, F> {
+    fn visit(&mut self, state: &S) -> bool {
+        self.seen.insert((self.key)(state))
+    }
+}
+
+/// Tells states apart by a dense index, such as the position in a grid.
+pub struct ByIndex<F> {
+    seen: Vec<bool>,
+    index: F,
+}
+
+impl<F> ByIndex<F> {
+    pub fn new(len: usize, index: F) -> ByIndex<F> {
+        ByIndex {
+            seen: vec![false; len],
+            index,
+        }
+    }
+}
+
+impl<S, F: FnMut(&S) -> usize> Visited<S> for ByIndex<F> {
+    fn visit(&mut self, state: &S) -> bool {
+        let index = (self.index)(state);
+        if index >= self.seen.len() {
+            self.seen.resize(index + 1, false);
+        }
+
+        !std::mem::replace(&mut self.seen[index], true)
+    }
+}
+
+struct Node<S, C> {
+    state: S,
+    parent: Option<usize>,
+    cost: C,
+}
+
+fn path<S, C>(nodes: Vec<Node<S, C>>, index: usize) -> Vec<S> {
+    let mut indices = vec![index];
+    while let Some(parent) = nodes[*indices.last().unwrap()].parent {
+        indices.push(parent);
+    }
+
+    let mut states: Vec<Option<S>> = nodes.into_iter().map(|n| Some(n.state)).collect();
+    indices
+        .iter()
+        .rev()
+        .map(|i| states[*i].take().unwrap())
+        .collect()
+}
+
+/// Finds the path with the fewest steps from `start` to a state where `goal` holds.
+pub fn bfs<S, I, F, G, V>(
+    start: S,
+    mut successors: F,
+    mut goal: G,
+    mut visited: V,
+) -> Option<Vec<S>>
+where
+    I: IntoIterator<Item = S>,
+    F: FnMut(&S) -> I,
+    G: FnMut(&S) -> bool,
+    V: Visited<S>,
+{
+    visited.visit(&start);
+    let mut nodes = vec![Node {
+        state: start,
+        parent: None,
+        cost: (),
+    }];
+
+    let mut i = 0;
+    while i < nodes.len() {
+        if goal(&nodes[i].state) {
+            return Some(path(nodes, i));
+        }
+
+        for next in successors(&nodes[i].state) {
+            if visited.visit(&next) {
+                nodes.push(Node {
+                    state: next,
+                    parent: Some(i),
+                    cost: (),
+                });
+            }
+        }
+
+        i += 1;
+    }
+
+    None
+}
+
+/// Finds any path from `start` to a state where `goal` holds, going deep first.
+pub fn dfs<S, I, F, G, V>(
+    start: S,
+    mut successors: F,
+    mut goal: G,
+    mut visited: V,
+) -> Option<Vec<S>>
+where
+    I: IntoIterator<Item = S>,
+    F: FnMut(&S) -> I,
+    G: FnMut(&S) -> bool,
+    V: Visited<S>,
+{
+    let mut nodes = vec![Node {
+        state: start,
+        parent: None,
+        cost: (),
+    }];
+    let mut stack = vec![0];
+
+    while let Some(i) = stack.pop() {
+        if !visited.visit(&nodes[i].state) {
+            continue;
+        }
+        if goal(&nodes[i].state) {
+            return Some(path(nodes, i));
+        }
+
+        let first = nodes.len();
+        for next in successors(&nodes[i].state) {
+            nodes.push(Node {
+                state: next,
+                parent: Some(i),
+                cost: (),
+            });
+        }
+
+        // Reversed so that the first successor is explored first.
+        stack.extend((first..nodes.len()).rev());
+    }
+
+    None
+}
+
+struct Entry<C> {
+    priority: C,
+    index: usize,
+}
+
+impl<C: Ord> Ord for Entry<C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .cmp(&self.priority)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl<C: Ord> PartialOrd for Entry<C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<C: Ord> PartialEq for Entry<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<C: Ord> Eq for Entry<C> {}
+
+/// Finds the cheapest path from `start` to a state where `goal` holds, where `successors`
+/// gives the cost of each step along with the state.
+pub fn dijkstra<S, C, I, F, G, V>(
+    start: S,
+    successors: F,
+    goal: G,
+    visited: V,
+) -> Option<(C, Vec<S>)>
+where
+    C: Copy + Ord + Add<Output = C> + Default,
+    I: IntoIterator<Item = (S, C)>,
+    F: FnMut(&S) -> I,
+    G: FnMut(&S) -> bool,
+    V: Visited<S>,
+{
+    astar(start, successors, |_| C::default(), goal, visited)
+}
+
+/// Like `dijkstra`, but guided by `heuristic`. For the path to be the cheapest, the
+/// heuristic must never drop by more than the cost of a step, nor be above zero at the goal.
+pub fn astar<S, C, I, F, H, G, V>(
+    start: S,
+    mut successors: F,
+    mut heuristic: H,
+    mut goal: G,
+    mut visited: V,
+) -> Option<(C, Vec<S>)>
+where
+    C: Copy + Ord + Add<Output = C> + Default,
+    I: IntoIterator<Item = (S, C)>,
+    F: FnMut(&S) -> I,
+    H: FnMut(&S) -> C,
+    G: FnMut(&S) -> bool,
+    V: Visited<S>,
+{
+    let mut heap = BinaryHeap::with_capacity(64);
+    heap.push(Entry {
+        priority: heuristic(&start),
+        index: 0,
+    });
+    let mut nodes = vec![Node {
+        state: start,
+        parent: None,
+        cost: C::default(),
+    }];
+
+    while let Some(Entry { index, .. }) = heap.pop() {
+        if !visited.visit(&nodes[index].state) {
+            continue;
+        }
+        if goal(&nodes[index].state) {
+            let cost = nodes[index].cost;
+            return Some((cost, path(nodes, index)));
+        }
+
+        let cost = nodes[index].cost;
+        for (next, step) in successors(&nodes[index].state) {
+            heap.push(Entry {
+                priority: cost + step + heuristic(&next),
+                index: nodes.len(),
+            });
+            nodes.push(Node {
+                state: next,
+                parent: Some(index),
+                cost: cost + step,
+            });
+        }
+    }
+
+    None
+}
+
+/// Goes through every state reachable from `start` and returns the one with the highest
+/// score. A state and everything below it are skipped if `bound` says none of them can
+/// beat the best score found so far.
+pub fn dfs_best<S, C, I, F, K, B>(start: S, mut successors: F, mut score: K, mut bound: B) -> (C, S)
+where
+    C: Ord,
+    I: IntoIterator<Item = S>,
+    F: FnMut(&S) -> I,
+    K: FnMut(&S) -> C,
+    B: FnMut(&S) -> C,
+{
+    let mut stack = Vec::with_capacity(64);
+    stack.extend(successors(&start));
+    let mut best = (score(&start), start);
+
+    while let Some(state) = stack.pop() {
+        if bound(&state) <= best.0 {
+            continue;
+        }
+
+        stack.extend(successors(&state));
+
+        let state_score = score(&state);
+        if state_score > best.0 {
+            best = (state_score, state);
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAZE: &[&str] = &["S.#....", ".##.##.", "...#...", ".#...#G"];
+
+    fn neighbors(&(x, y): &(usize, usize)) -> Vec<(usize, usize)> {
+        let mut res = Vec::with_capacity(4);
+        if x > 0 {
+            res.push((x - 1, y));
+        }
+        if y > 0 {
+            res.push((x, y - 1));
+        }
+        if x + 1 < MAZE[0].len() {
+            res.push((x + 1, y));
+        }
+        if y + 1 < MAZE.len() {
+            res.push((x, y + 1));
+        }
+
+        res.retain(|(x, y)| MAZE[*y].as_bytes()[*x] != b'#');
+        res
+    }
+
+    #[test]
+    fn test_bfs_dfs() {
+        let goal = |p: &(usize, usize)| *p == (6, 3);
+
+        let path = bfs((0, 0), neighbors, goal, FxHashSet::default()).unwrap();
+        assert_eq!(path.len(), 12);
+        assert_eq!(path[0], (0, 0));
+        assert_eq!(path[11], (6, 3));
+
+        let by_index = ByIndex::new(0, |(x, y): &(usize, usize)| y * 7 + x);
+        let path = dfs((0, 0), neighbors, goal, by_index).unwrap();
+        assert!(path.len() >= 12);
+        assert!(path.windows(2).all(|w| neighbors(&w[0]).contains(&w[1])));
+
+        assert_eq!(
+            bfs(
+                (0, 0),
+                neighbors,
+                |_| false,
+                ByKey::new(|p: &(usize, usize)| *p)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dijkstra_astar() {
+        // Moving right is cheap, moving down is expensive.
+        let successors = |&(x, y): &(u32, u32)| {
+            let mut res = Vec::with_capacity(2);
+            if x < 4 {
+                res.push(((x + 1, y), 1));
+            }
+            if y < 4 {
+                res.push(((x, y + 1), 3 + x));
+            }
+            res
+        };
+
+        let (cost, path) =
+            dijkstra((0, 0), successors, |p| *p == (4, 4), FxHashSet::default()).unwrap();
+        assert_eq!(cost, 16);
+        assert_eq!(&path[..2], &[(0, 0), (0, 1)]);
+
+        let heuristic = |&(x, y): &(u32, u32)| (4 - x) + (4 - y) * 3;
+        let (cost, _) = astar(
+            (0, 0),
+            successors,
+            heuristic,
+            |p| *p == (4, 4),
+            FxHashSet::default(),
+        )
+        .unwrap();
+        assert_eq!(cost, 16);
+    }
+
+    #[test]
+    fn test_dfs_best() {
+        // Pick items summing to at most 10, maximizing the sum of squares.
+        let items = [6u32, 5, 4, 3];
+        let successors = |&(i, weight, value): &(usize, u32, u32)| {
+            (i..items.len())
+                .filter(|j| weight + items[*j] <= 10)
+                .map(|j| (j + 1, weight + items[j], value + items[j] * items[j]))
+                .collect::<Vec<_>>()
+        };
+        let bound = |&(i, _, value): &(usize, u32, u32)| {
+            value + items[i..].iter().map(|n| n * n).sum::<u32>()
+        };
+
+        let (best, state) = dfs_best((0, 0, 0), successors, |s| s.2, bound);
+        assert_eq!(best, 52);
+        assert_eq!(state, (3, 10, 52));
+    }
+}
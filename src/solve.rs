@@ -0,0 +1,295 @@
+use crate::bitset::{BitSet, HeapBitSet, InlineBitSet};
+
+/// A set of candidate slots for one item.
+pub trait Mask {
+    fn contains(&self, slot: usize) -> bool;
+}
+
+macro_rules! impl_mask_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl Mask for $t {
+                fn contains(&self, slot: usize) -> bool {
+                    slot < <$t>::BITS as usize && (self >> slot) & 1 == 1
+                }
+            }
+        )*
+    };
+}
+
+impl_mask_for_int!(u8, u16, u32, u64, u128, usize);
+
+/// Words of 64 bits, with the lowest bit of the first word being slot 0.
+impl Mask for [u64] {
+    fn contains(&self, slot: usize) -> bool {
+        self.get(slot / 64)
+            .map(|word| (word >> (slot % 64)) & 1 == 1)
+            .unwrap_or(false)
+    }
+}
+
+impl Mask for Vec<u64> {
+    fn contains(&self, slot: usize) -> bool {
+        Mask::contains(self.as_slice(), slot)
+    }
+}
+
+impl Mask for [bool] {
+    fn contains(&self, slot: usize) -> bool {
+        self.get(slot).cloned().unwrap_or(false)
+    }
+}
+
+impl Mask for Vec<bool> {
+    fn contains(&self, slot: usize) -> bool {
+        Mask::contains(self.as_slice(), slot)
+    }
+}
+
+impl<const N: usize> Mask for InlineBitSet<N> {
+    fn contains(&self, slot: usize) -> bool {
+        BitSet::contains(self, slot)
+    }
+}
+
+impl Mask for HeapBitSet {
+    fn contains(&self, slot: usize) -> bool {
+        BitSet::contains(self, slot)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SolveError {
+    /// No assignment gives every item its own slot.
+    Contradiction,
+    /// More than one assignment works. The first two that were found are included.
+    Ambiguous(Vec<usize>, Vec<usize>),
+}
+
+#[derive(Clone)]
+struct Domains {
+    words: usize,
+    bits: Vec<u64>,
+}
+
+impl Domains {
+    fn new<M: Mask + ?Sized>(masks: &[&M], slots: usize) -> Domains {
+        let words = slots.div_ceil(64);
+        let mut bits = vec![0u64; masks.len() * words];
+
+        for (item, mask) in masks.iter().enumerate() {
+            for slot in 0..slots {
+                if mask.contains(slot) {
+                    bits[item * words + slot / 64] |= 1 << (slot % 64);
+                }
+            }
+        }
+
+        Domains { words, bits }
+    }
+
+    fn item(&self, item: usize) -> &[u64] {
+        &self.bits[item * self.words..(item + 1) * self.words]
+    }
+
+    fn count(&self, item: usize) -> u32 {
+        self.item(item).iter().map(|w| w.count_ones()).sum()
+    }
+
+    fn first(&self, item: usize) -> Option<usize> {
+        self.item(item)
+            .iter()
+            .enumerate()
+            .find(|(_, w)| **w != 0)
+            .map(|(i, w)| i * 64 + w.trailing_zeros() as usize)
+    }
+
+    fn has(&self, item: usize, slot: usize) -> bool {
+        (self.bits[item * self.words + slot / 64] >> (slot % 64)) & 1 == 1
+    }
+
+    fn remove(&mut self, item: usize, slot: usize) {
+        self.bits[item * self.words + slot / 64] &= !(1 << (slot % 64));
+    }
+
+    fn assign(&mut self, item: usize, slot: usize) {
+        for word in self.bits[item * self.words..(item + 1) * self.words].iter_mut() {
+            *word = 0;
+        }
+        self.bits[item * self.words + slot / 64] |= 1 << (slot % 64);
+    }
+}
+
+struct Solver {
+    items: usize,
+    slots: usize,
+    limit: usize,
+    solutions: Vec<Vec<usize>>,
+}
+
+impl Solver {
+    /// Assigns every item with a single candidate and, when every slot has to be used,
+    /// every slot with a single candidate item. Returns false on a contradiction.
+    fn propagate(&self, domains: &mut Domains, assigned: &mut [bool]) -> bool {
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for (item, is_assigned) in assigned.iter_mut().enumerate() {
+                let count = domains.count(item);
+                if count == 0 {
+                    return false;
+                }
+                if count > 1 || *is_assigned {
+                    continue;
+                }
+
+                let slot = domains.first(item).unwrap();
+                *is_assigned = true;
+                changed = true;
+
+                for other in 0..self.items {
+                    if other != item && domains.has(other, slot) {
+                        domains.remove(other, slot);
+                    }
+                }
+            }
+
+            if self.items != self.slots {
+                continue;
+            }
+
+            for slot in 0..self.slots {
+                let mut candidates = (0..self.items).filter(|item| domains.has(*item, slot));
+
+                match (candidates.next(), candidates.next()) {
+                    (None, _) => return false,
+                    (Some(item), None) if !assigned[item] => {
+                        domains.assign(item, slot);
+                        changed = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        true
+    }
+
+    fn search(&mut self, mut domains: Domains, mut assigned: Vec<bool>) {
+        if self.solutions.len() >= self.limit || !self.propagate(&mut domains, &mut assigned) {
+            return;
+        }
+
+        let branch = (0..self.items)
+            .filter(|item| !assigned[*item])
+            .min_by_key(|item| domains.count(*item));
+
+        match branch {
+            None => {
+                let solution = (0..self.items)
+                    .map(|item| domains.first(item).unwrap())
+                    .collect();
+                self.solutions.push(solution);
+            }
+            Some(item) => {
+                for slot in 0..self.slots {
+                    if domains.has(item, slot) {
+                        let mut domains = domains.clone();
+                        domains.assign(item, slot);
+                        self.search(domains, assigned.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    fn run<M: Mask + ?Sized>(masks: &[&M], slots: usize, limit: usize) -> Vec<Vec<usize>> {
+        let mut solver = Solver {
+            items: masks.len(),
+            slots,
+            limit,
+            solutions: Vec::new(),
+        };
+
+        if masks.len() <= slots {
+            solver.search(Domains::new(masks, slots), vec![false; masks.len()]);
+        }
+
+        solver.solutions
+    }
+}
+
+/// Finds every way to give each item a different slot among its candidates. The result
+/// holds the slot of each item.
+pub fn solve_all<M: Mask>(masks: &[M], slots: usize) -> Vec<Vec<usize>> {
+    let masks: Vec<&M> = masks.iter().collect();
+
+    Solver::run(&masks, slots, usize::MAX)
+}
+
+/// Finds the only way to give each item a different slot among its candidates.
+pub fn solve<M: Mask>(masks: &[M], slots: usize) -> Result<Vec<usize>, SolveError> {
+    let masks: Vec<&M> = masks.iter().collect();
+    let mut solutions = Solver::run(&masks, slots, 2);
+
+    match solutions.len() {
+        0 => Err(SolveError::Contradiction),
+        1 => Ok(solutions.pop().unwrap()),
+        _ => {
+            let second = solutions.pop().unwrap();
+            let first = solutions.pop().unwrap();
+
+            Err(SolveError::Ambiguous(first, second))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_propagation() {
+        // The day 16 example, where fields are items and rules are slots.
+        assert_eq!(solve(&[0b010u32, 0b011, 0b111], 3), Ok(vec![1, 0, 2]));
+        // Fewer items than slots.
+        assert_eq!(solve(&[0b0110u8, 0b0100, 0b0001], 4), Ok(vec![1, 2, 0]));
+    }
+
+    #[test]
+    fn test_backtracking() {
+        // Every item has two candidates and every slot two items, so nothing propagates.
+        let masks = [0b011u8, 0b110, 0b101];
+
+        assert_eq!(solve_all(&masks, 3), vec![vec![0, 1, 2], vec![1, 2, 0]]);
+        assert_eq!(
+            solve(&masks, 3),
+            Err(SolveError::Ambiguous(vec![0, 1, 2], vec![1, 2, 0]))
+        );
+    }
+
+    #[test]
+    fn test_contradiction() {
+        assert_eq!(solve(&[0b01u8, 0b01], 2), Err(SolveError::Contradiction));
+        assert_eq!(solve(&[0b1u8, 0b1, 0b1], 1), Err(SolveError::Contradiction));
+        assert!(solve_all(&[0b011u8, 0b011, 0b011], 3).is_empty());
+    }
+
+    #[test]
+    fn test_wide_masks() {
+        let mut masks = vec![vec![0u64; 3]; 130];
+        for (item, mask) in masks.iter_mut().enumerate() {
+            let slot = 129 - item;
+            mask[slot / 64] |= 1 << (slot % 64);
+            if item > 0 {
+                let slot = 130 - item;
+                mask[slot / 64] |= 1 << (slot % 64);
+            }
+        }
+
+        let expected: Vec<usize> = (0..130).map(|item| 129 - item).collect();
+        assert_eq!(solve(&masks, 130), Ok(expected));
+    }
+}
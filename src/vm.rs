@@ -0,0 +1,418 @@
+use crate::cfg::{Cfg, ControlFlow, Flow};
+use crate::parsers::parse_i32;
+use std::fmt;
+
+/// An instruction for the handheld game console from 2020 day 8.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Instruction {
+    Nop(i32),
+    Acc(i32),
+    Jmp(i32),
+}
+
+impl Instruction {
+    pub fn apply(&self, state: State) -> State {
+        match *self {
+            Instruction::Nop(_) => State {
+                pc: state.pc.wrapping_add(1),
+                acc: state.acc,
+            },
+            Instruction::Acc(n) => State {
+                pc: state.pc.wrapping_add(1),
+                acc: state.acc + n,
+            },
+            Instruction::Jmp(n) => State {
+                // A negative target wraps around to a huge pc, which is out of range.
+                pc: state.pc.wrapping_add(n as isize as usize),
+                acc: state.acc,
+            },
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Instruction> {
+        if line.len() < 5 {
+            return None;
+        }
+
+        let n = parse_i32(&line[4..]);
+
+        match &line[0..3] {
+            "nop" => Some(Instruction::Nop(n)),
+            "acc" => Some(Instruction::Acc(n)),
+            "jmp" => Some(Instruction::Jmp(n)),
+            _ => None,
+        }
+    }
+}
+
+impl ControlFlow for Instruction {
+    fn flow(&self) -> Flow {
+        match *self {
+            Instruction::Nop(_) | Instruction::Acc(_) => Flow::Next,
+            Instruction::Jmp(n) => Flow::Jump(n as isize),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Nop(n) => write!(f, "nop {:+}", n),
+            Instruction::Acc(n) => write!(f, "acc {:+}", n),
+            Instruction::Jmp(n) => write!(f, "jmp {:+}", n),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct State {
+    pub pc: usize,
+    pub acc: i32,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Halt {
+    Terminated,
+    OutOfRange,
+    Loop,
+    StepLimit,
+}
+
+/// Decides whether a run should stop before executing the instruction at `state.pc`. The
+/// run always stops when the pc leaves the program, so conditions only need to handle the
+/// cases where it could otherwise keep going.
+pub trait HaltCondition {
+    fn check(&mut self, state: &State, steps: usize) -> Option<Halt>;
+}
+
+impl HaltCondition for () {
+    fn check(&mut self, _state: &State, _steps: usize) -> Option<Halt> {
+        None
+    }
+}
+
+impl<A, B> HaltCondition for (A, B)
+where
+    A: HaltCondition,
+    B: HaltCondition,
+{
+    fn check(&mut self, state: &State, steps: usize) -> Option<Halt> {
+        self.0.check(state, steps).or_else(|| self.1.check(state, steps))
+    }
+}
+
+/// Halts when an instruction is about to be executed a second time.
+pub struct LoopDetector {
+    seen: Vec<bool>,
+}
+
+impl LoopDetector {
+    pub fn new(len: usize) -> LoopDetector {
+        LoopDetector {
+            seen: vec![false; len],
+        }
+    }
+}
+
+impl HaltCondition for LoopDetector {
+    fn check(&mut self, state: &State, _steps: usize) -> Option<Halt> {
+        if self.seen[state.pc] {
+            Some(Halt::Loop)
+        } else {
+            self.seen[state.pc] = true;
+            None
+        }
+    }
+}
+
+pub struct StepBudget(pub usize);
+
+impl HaltCondition for StepBudget {
+    fn check(&mut self, _state: &State, steps: usize) -> Option<Halt> {
+        if steps >= self.0 {
+            Some(Halt::StepLimit)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TraceEntry {
+    pub step: usize,
+    pub state: State,
+    pub instruction: Instruction,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Trace {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(
+                f,
+                "{:>6} | pc={:<5} acc={:<7} | {}",
+                entry.step, entry.state.pc, entry.state.acc, entry.instruction
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Mutation {
+    JmpToNop,
+    NopToJmp,
+    AccToNop,
+    Replace(Instruction),
+}
+
+impl Mutation {
+    pub fn apply(&self, instruction: Instruction) -> Option<Instruction> {
+        match (*self, instruction) {
+            (Mutation::JmpToNop, Instruction::Jmp(n)) => Some(Instruction::Nop(n)),
+            (Mutation::NopToJmp, Instruction::Nop(n)) => Some(Instruction::Jmp(n)),
+            (Mutation::AccToNop, Instruction::Acc(n)) => Some(Instruction::Nop(n)),
+            (Mutation::Replace(replacement), _) if replacement != instruction => Some(replacement),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Repair {
+    pub pc: usize,
+    pub mutation: Mutation,
+    pub replacement: Instruction,
+    pub state: State,
+    pub path: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+}
+
+impl Program {
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// Executes the instruction at `state.pc`, or returns `None` if there is none.
+    pub fn step(&self, state: State) -> Option<State> {
+        self.instructions.get(state.pc).map(|i| i.apply(state))
+    }
+
+    pub fn run(&self, state: State, cond: &mut impl HaltCondition) -> (State, Halt) {
+        self.run_with(state, cond, |_| {})
+    }
+
+    pub fn run_traced(&self, state: State, cond: &mut impl HaltCondition) -> (State, Halt, Trace) {
+        let mut trace = Trace::default();
+        let (state, halt) = self.run_with(state, cond, |entry| trace.entries.push(entry));
+
+        (state, halt, trace)
+    }
+
+    fn run_with(
+        &self,
+        mut state: State,
+        cond: &mut impl HaltCondition,
+        mut on_step: impl FnMut(TraceEntry),
+    ) -> (State, Halt) {
+        let len = self.instructions.len();
+        let mut steps = 0;
+
+        loop {
+            if state.pc == len {
+                return (state, Halt::Terminated);
+            } else if state.pc > len {
+                return (state, Halt::OutOfRange);
+            }
+            if let Some(halt) = cond.check(&state, steps) {
+                return (state, halt);
+            }
+
+            let instruction = self.instructions[state.pc];
+            on_step(TraceEntry {
+                step: steps,
+                state,
+                instruction,
+            });
+
+            state = instruction.apply(state);
+            steps += 1;
+        }
+    }
+
+    pub fn patched(&self, pc: usize, instruction: Instruction) -> Program {
+        let mut instructions = self.instructions.clone();
+        instructions[pc] = instruction;
+
+        Program { instructions }
+    }
+
+    /// Finds every single-instruction mutation that makes the program terminate. The
+    /// instructions that can reach the end unmodified are found by walking the control-flow
+    /// graph backwards from it, so only the pcs visited before the first loop need to be tried,
+    /// and a repair's run goes on from the state the mutated instruction was reached in.
+    ///
+    /// A program that already terminates has nothing to repair and yields no repairs.
+    pub fn repairs(&self, mutations: &[Mutation]) -> Vec<Repair> {
+        let len = self.instructions.len();
+        let terminating = Cfg::build(&self.instructions).terminating();
+
+        let mut cond = LoopDetector::new(len);
+        let (_, halt, trace) = self.run_traced(State::default(), &mut cond);
+        if halt == Halt::Terminated {
+            return Vec::new();
+        }
+
+        let mut repairs = Vec::new();
+        for (i, entry) in trace.entries.iter().enumerate() {
+            for mutation in mutations.iter() {
+                let replacement = match mutation.apply(entry.instruction) {
+                    Some(replacement) => replacement,
+                    None => continue,
+                };
+
+                let next = replacement.apply(entry.state);
+                if next.pc > len || (next.pc < len && !terminating[next.pc]) {
+                    continue;
+                }
+
+                // None of the pcs visited so far can reach the end, so the rest of the run
+                // never gets back to the mutated instruction and needs no patched program.
+                let (state, _, rest) = self.run_traced(next, &mut ());
+
+                repairs.push(Repair {
+                    pc: entry.state.pc,
+                    mutation: *mutation,
+                    replacement,
+                    state,
+                    path: trace.entries[..=i]
+                        .iter()
+                        .chain(rest.entries.iter())
+                        .map(|e| e.state.pc)
+                        .collect(),
+                });
+            }
+        }
+
+        repairs
+    }
+
+    pub fn from(instructions: Vec<Instruction>) -> Program {
+        Program { instructions }
+    }
+
+    pub fn parse(s: &str) -> Program {
+        let mut instructions = Vec::with_capacity(1024);
+
+        for line in s.lines() {
+            if line.is_empty() {
+                break;
+            }
+
+            match Instruction::parse(line) {
+                Some(instruction) => instructions.push(instruction),
+                None => panic!("Unknown instruction: {}", line),
+            }
+        }
+
+        Program { instructions }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6
+";
+
+    #[test]
+    fn test_loop_detection() {
+        let program = Program::parse(EXAMPLE);
+        let mut cond = LoopDetector::new(program.len());
+
+        assert_eq!(
+            program.run(State::default(), &mut cond),
+            (State { pc: 1, acc: 5 }, Halt::Loop)
+        );
+    }
+
+    #[test]
+    fn test_step_budget() {
+        let program = Program::parse(EXAMPLE);
+        let (state, halt, trace) = program.run_traced(State::default(), &mut StepBudget(3));
+
+        assert_eq!(halt, Halt::StepLimit);
+        assert_eq!(state, State { pc: 6, acc: 1 });
+        assert_eq!(trace.entries.len(), 3);
+        assert_eq!(trace.entries[2].instruction, Instruction::Jmp(4));
+        assert_eq!(
+            trace.to_string().lines().nth(1).unwrap(),
+            "     1 | pc=1     acc=0       | acc +1"
+        );
+    }
+
+    #[test]
+    fn test_termination() {
+        let mut program = Program::parse(EXAMPLE);
+        program.instructions[7] = Instruction::Nop(-4);
+
+        let mut cond = (LoopDetector::new(program.len()), StepBudget(1000));
+        assert_eq!(
+            program.run(State::default(), &mut cond),
+            (State { pc: 9, acc: 8 }, Halt::Terminated)
+        );
+
+        program.instructions[0] = Instruction::Jmp(-1);
+        assert_eq!(program.run(State::default(), &mut ()).1, Halt::OutOfRange);
+    }
+
+    #[test]
+    fn test_repairs() {
+        let program = Program::parse(EXAMPLE);
+        let repairs = program.repairs(&[Mutation::JmpToNop, Mutation::NopToJmp]);
+
+        assert_eq!(
+            repairs,
+            vec![Repair {
+                pc: 7,
+                mutation: Mutation::JmpToNop,
+                replacement: Instruction::Nop(-4),
+                state: State { pc: 9, acc: 8 },
+                path: vec![0, 1, 2, 6, 7, 8],
+            }]
+        );
+
+        let repairs = program.repairs(&[Mutation::Replace(Instruction::Acc(0))]);
+        assert_eq!(repairs.iter().map(|r| r.pc).collect::<Vec<_>>(), vec![7]);
+        assert!(program
+            .patched(7, Instruction::Nop(-4))
+            .repairs(&[Mutation::JmpToNop])
+            .is_empty());
+    }
+}
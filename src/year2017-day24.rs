@@ -1,4 +1,6 @@
 use common::aoc::{load_input, print_result, print_time, run_many, run_once};
+use common::bitset::{BitSet, HeapBitSet};
+use common::search::dfs_best;
 
 const SAMPLE_COMPONENTS: &[(u32, u32); 8] = &[
     (0, 2),
@@ -36,140 +38,106 @@ fn main() {
 }
 
 fn part1(components: &[(u32, u32)]) -> u32 {
-    let mut stack = Vec::with_capacity(64);
-    let mut used = vec![false; components.len()];
-    let mut max_strength = 0;
-
-    for i in 0..components.len() {
-        let (l, r) = components[i];
-        let left_magnetic = l == 0;
-        let right_magnetic = r == 0;
-        if !left_magnetic && !right_magnetic {
-            continue;
-        }
-
-        stack.push((i, 0usize, left_magnetic));
-        used[i] = true;
-
-        while !stack.is_empty() {
-            let (i, pos, is_right) = stack.last().cloned().unwrap();
-            let (li, ri) = components[i];
-            let mut dead_end = true;
-
-            let connector = if is_right {ri} else {li};
-
-            for j in pos..components.len() {
-                if used[j] {
-                    continue;
-                }
-                let (l, r) = components[j];
-
-                let left_match = connector == l;
-                let right_match = connector == r;
-
-                if left_match || right_match {
-                    *stack.last_mut().unwrap() = (i, j + 1, is_right);
-                    stack.push((j, 0usize, left_match));
-                    used[j] = true;
-                    dead_end = false;
-                    break;
-                }
-            }
-
-            if dead_end {
-                if pos == 0 {
-                    let strength = stack.iter()
-                        .map(|(i, _, _)| &components[*i])
-                        .map(|(l, r)| *l + *r)
-                        .sum::<u32>();
-
-                    if strength > max_strength {
-                        max_strength = strength;
-                    }
-                }
-
-                used[i] = false;
-                stack.pop();
-            }
-        }
-
-        used[i] = false;
-    }
-
-    max_strength
+    let (strength, _) = dfs_best(
+        Bridge::new(components),
+        |bridge| bridge.extensions(components),
+        |bridge| bridge.strength,
+        |bridge| bridge.strength + bridge.remaining,
+    );
+
+    strength
 }
 
 fn part2(components: &[(u32, u32)]) -> u32 {
-    let mut stack = Vec::with_capacity(64);
-    let mut used = vec![false; components.len()];
-    let mut max_strength = 0;
-    let mut max_length = 0;
-
-    for i in 0..components.len() {
-        let (l, r) = components[i];
-        let left_magnetic = l == 0;
-        let right_magnetic = r == 0;
-        if !left_magnetic && !right_magnetic {
-            continue;
-        }
-
-        stack.push((i, 0usize, left_magnetic));
-        used[i] = true;
-
-        while !stack.is_empty() {
-            let (i, pos, is_right) = stack.last().cloned().unwrap();
-            let (li, ri) = components[i];
-            let mut dead_end = true;
+    let ((_, strength), _) = dfs_best(
+        Bridge::new(components),
+        |bridge| bridge.extensions(components),
+        |bridge| (bridge.length, bridge.strength),
+        |bridge| {
+            (
+                bridge.length + bridge.unused,
+                bridge.strength + bridge.remaining,
+            )
+        },
+    );
+
+    strength
+}
 
-            let connector = if is_right {ri} else {li};
+#[derive(Clone)]
+struct Bridge {
+    used: HeapBitSet,
+    port: u32,
+    length: u32,
+    strength: u32,
+    /// The number of components left, and their total strength.
+    unused: u32,
+    remaining: u32,
+}
 
-            for j in pos..components.len() {
-                if used[j] {
-                    continue;
-                }
-                let (l, r) = components[j];
+impl Bridge {
+    fn new(components: &[(u32, u32)]) -> Bridge {
+        Bridge {
+            used: HeapBitSet::with_capacity(components.len()),
+            port: 0,
+            length: 0,
+            strength: 0,
+            unused: components.len() as u32,
+            remaining: components.iter().map(|(l, r)| l + r).sum(),
+        }
+    }
 
-                let left_match = connector == l;
-                let right_match = connector == r;
+    fn extensions(&self, components: &[(u32, u32)]) -> Vec<Bridge> {
+        let mut res = Vec::with_capacity(8);
 
-                if left_match || right_match {
-                    *stack.last_mut().unwrap() = (i, j + 1, is_right);
-                    stack.push((j, 0usize, left_match));
-                    used[j] = true;
-                    dead_end = false;
-                    break;
-                }
+        for (i, (l, r)) in components.iter().cloned().enumerate() {
+            if self.used.contains(i) || (l != self.port && r != self.port) {
+                continue;
             }
 
-            if dead_end {
-                if pos == 0 && stack.len() >= max_length {
-                    let strength = stack.iter()
-                        .map(|(i, _, _)| &components[*i])
-                        .map(|(l, r)| *l + *r)
-                        .sum::<u32>();
-
-                    if strength > max_strength || stack.len() > max_length {
-                        max_strength = strength;
-                        max_length = stack.len();
-                    }
-                }
-
-                used[i] = false;
-                stack.pop();
-            }
+            let mut bridge = self.clone();
+            bridge.used.insert(i);
+            bridge.port = if l == self.port { r } else { l };
+            bridge.length += 1;
+            bridge.strength += l + r;
+            bridge.unused -= 1;
+            bridge.remaining -= l + r;
+
+            res.push(bridge);
         }
 
-        used[i] = false;
+        res
     }
-
-    max_strength
 }
 
-
 fn parse_input(input: &str) -> Vec<(u32, u32)> {
-    input.lines()
+    input
+        .lines()
         .filter(|l| !l.is_empty())
         .map(|l| l.split('/'))
-        .map(|mut t| (t.next().unwrap().parse().unwrap(), t.next().unwrap().parse().unwrap()))
+        .map(|mut t| {
+            (
+                t.next().unwrap().parse().unwrap(),
+                t.next().unwrap().parse().unwrap(),
+            )
+        })
         .collect()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        let components = parse_input("0/2\n2/2\n2/3\n3/4\n3/5\n0/1\n10/1\n9/10\n");
+        assert_eq!(components, SAMPLE_COMPONENTS.to_vec());
+        assert_eq!(part1(&components), 31);
+        assert_eq!(part2(&components), 19);
+
+        // More components than fit in a couple of words.
+        let chain: Vec<(u32, u32)> = (0..200).map(|i| (i, i + 1)).collect();
+        assert_eq!(part1(&chain), 200 * 200);
+        assert_eq!(part2(&chain), 200 * 200);
+    }
+}